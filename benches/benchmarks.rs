@@ -1,13 +1,50 @@
 mod dnsgen_bench;
 mod model_bench;
-use dnsgen_bench::dnsgen_bench;
-use model_bench::insert;
+use dnsgen_bench::{dnsgen_bench, stream_bench};
+use model::Normalization;
+use model_bench::{insert, insert_urls, search_urls, urls};
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("insert", |b| b.iter(|| insert(black_box(2000))));
     c.bench_function("dnsgen", |b| b.iter(|| dnsgen_bench(black_box(100))));
+
+    let mut group = c.benchmark_group("dnsgen");
+    group.sample_size(10);
+    for n in [100, 300] {
+        group.bench_function(format!("collect {n}"), |b| {
+            b.iter(|| dnsgen_bench(black_box(n)))
+        });
+        group.bench_function(format!("stream {n}"), |b| {
+            b.iter(|| stream_bench(black_box(n)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("urls");
+    group.sample_size(10);
+
+    let assets = urls(100_000);
+    for normalization in [
+        Normalization::Strict,
+        Normalization::Heuristic,
+        Normalization::Pattern,
+    ] {
+        group.bench_function(format!("insert 100k {normalization:?}"), |b| {
+            b.iter_batched(
+                || assets.clone(),
+                |assets| insert_urls(assets, normalization),
+                BatchSize::LargeInput,
+            )
+        });
+
+        let program = insert_urls(assets.clone(), normalization);
+        group.bench_function(format!("search 100k {normalization:?}"), |b| {
+            b.iter(|| search_urls(black_box(&program), black_box(&assets)))
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
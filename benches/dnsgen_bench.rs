@@ -1,6 +1,6 @@
-use dnsgen::dnsgen;
+use dnsgen::{dnsgen, stream, Strategy};
 
-pub fn dnsgen_bench(n: i32) {
+fn inputs(n: i32) -> (Vec<String>, Vec<String>) {
     let mut subdomains = vec![];
     for i in 0..n {
         subdomains.push(format!("www{}.test.com", i))
@@ -11,5 +11,19 @@ pub fn dnsgen_bench(n: i32) {
         wl.push(format!("word{}", i))
     }
 
+    (subdomains, wl)
+}
+
+pub fn dnsgen_bench(n: i32) {
+    let (subdomains, wl) = inputs(n);
     dnsgen(subdomains, wl);
 }
+
+pub fn stream_bench(n: i32) -> usize {
+    let (subdomains, words) = inputs(n);
+    let strategy = Strategy {
+        words,
+        ..Default::default()
+    };
+    stream(subdomains, &strategy).count()
+}
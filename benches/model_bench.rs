@@ -10,3 +10,36 @@ pub fn insert(i: i32) {
         luna.insert_asset(asset, Some(pr)).unwrap();
     }
 }
+
+pub fn urls(n: usize) -> Vec<Asset> {
+    (0..n)
+        .map(|i| {
+            Asset::from_str(&format!(
+                "https://sub{}.test.com/api/v{}/item{}/{i}?id={i}&page{}=1",
+                i % 50,
+                i % 7,
+                i % 1000,
+                i % 3
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+pub fn insert_urls(urls: Vec<Asset>, normalization: Normalization) -> Program {
+    let mut program = Program {
+        normalization: Some(normalization),
+        ..Default::default()
+    };
+
+    for url in urls {
+        program.insert_asset(url);
+    }
+    program
+}
+
+pub fn search_urls(program: &Program, urls: &[Asset]) -> usize {
+    urls.iter()
+        .filter(|a| program.asset_position(&a.name).is_some())
+        .count()
+}
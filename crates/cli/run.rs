@@ -1,11 +1,16 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgEnum, Parser, Subcommand};
 use colored::*;
-use dnsgen::dnsgen;
+use dnsgen::{learn, roots, stream, Strategy, ENVS, REGIONS};
 use log::{debug, error, info, warn};
 use model::*;
 use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
     path::PathBuf,
+    str::FromStr,
     sync::{atomic::AtomicBool, Arc},
+    time::Duration,
 };
 
 #[derive(Parser)]
@@ -45,6 +50,14 @@ pub enum Cli {
     Stat(LunaStat),
     Dnsgen(Box<Dnsgen>),
     Report(Report),
+    Normalize(Box<Normalize>),
+    Resolve(Box<Resolve>),
+    #[clap(subcommand)]
+    Program(ProgramCli),
+    #[clap(subcommand)]
+    Tag(TagCli),
+    #[clap(subcommand)]
+    View(ViewCli),
     #[clap(subcommand)]
     Server(Server),
 }
@@ -55,6 +68,26 @@ pub struct Find {
     pub field: Field,
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: u8,
+    #[clap(long, help = "Print these fields, e.g. url,sc,title,program")]
+    pub fields: Option<Fields>,
+    #[clap(long, arg_enum, ignore_case = true)]
+    pub format: Option<Format>,
+    #[clap(long, conflicts_with_all = &["fields", "format"], help = "e.g. '{url} {sc}'")]
+    pub template: Option<Template>,
+    #[clap(
+        long,
+        arg_enum,
+        ignore_case = true,
+        conflicts_with_all = &["fields", "format", "template"],
+        help = "Group tags and values"
+    )]
+    pub group: Option<Group>,
+    #[clap(
+        long,
+        conflicts_with_all = &["fields", "format", "template", "group"],
+        help = "Count values, the most found first"
+    )]
+    pub count: bool,
     #[clap(flatten)]
     pub filter: Filter,
 }
@@ -63,18 +96,24 @@ pub struct Find {
 pub struct Check {
     #[clap(short, long)]
     pub script: Option<PathBuf>,
+    #[clap(long, help = "Report assets in more than one program")]
+    pub conflicts: bool,
 }
 
 #[derive(Debug, Parser)]
 pub struct LunaStat {
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: u8,
+    #[clap(long, arg_enum, ignore_case = true)]
+    pub format: Option<Format>,
+    #[clap(flatten)]
+    pub opt: StatOptions,
 }
 
 #[derive(Debug, Parser)]
 pub enum Insert {
     Program(InsertProgram),
-    Asset(InsertAsset),
+    Asset(Box<InsertAsset>),
     Tag(InsertTag),
 }
 
@@ -103,6 +142,168 @@ pub struct InsertTag {
 pub struct Dnsgen {
     #[clap(short, long)]
     pub wl: Option<PathBuf>,
+    #[clap(long, help = "Add environment names to the words, e.g. staging")]
+    pub envs: bool,
+    #[clap(long, help = "Add region names to the words, e.g. us-east-1")]
+    pub regions: bool,
+    #[clap(
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        default_values = &["replace", "prepend", "append"],
+        help = "Ways of putting words in subdomains"
+    )]
+    pub ops: Vec<Permutation>,
+    #[clap(
+        long = "sep",
+        use_value_delimiter = true,
+        default_values = &["", "-", "."],
+        help = "Separators of prepended and appended words"
+    )]
+    pub separators: Vec<String>,
+    #[clap(
+        long,
+        help = "Numbers up to RANGE away instead of replacing the last digit with 0-9"
+    )]
+    pub range: Option<u64>,
+    #[clap(
+        long,
+        conflicts_with_all = &["ops", "separators", "range"],
+        help = "Recombine tokens and patterns of the subdomains instead of the strategy ops"
+    )]
+    pub learn: bool,
+    #[clap(long, help = "Most candidates of each batch")]
+    pub limit: Option<usize>,
+    #[clap(
+        long,
+        help = "Only use subdomains of the same program, one batch per program"
+    )]
+    pub per_program: bool,
+    #[clap(long, help = "Insert candidates as unverified assets")]
+    pub insert: bool,
+    #[clap(long, help = "Write candidates to a file per root domain")]
+    pub out_dir: Option<PathBuf>,
+    #[clap(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Permutation {
+    Replace,
+    Prepend,
+    Append,
+    Insert,
+    Level,
+}
+
+#[derive(Parser)]
+pub enum ProgramCli {
+    /// Insert or update programs of a public program list, e.g. hackerone_data.json of bounty-targets-data
+    Import(ProgramImport),
+    Rename {
+        name: String,
+        to: String,
+    },
+    /// Merge the first program into the second one
+    Merge {
+        name: String,
+        into: String,
+    },
+    /// Move a domain or cidr with its subdomains and urls to another program
+    MoveAsset {
+        asset: AssetName,
+        to: String,
+    },
+}
+
+#[derive(Parser)]
+pub struct ProgramImport {
+    pub file: PathBuf,
+    #[clap(
+        long,
+        help = "Platform of the programs, guessed from their urls if empty"
+    )]
+    pub platform: Option<String>,
+    #[clap(long, help = "Only programs that pay bounties")]
+    pub bounty_only: bool,
+}
+
+#[derive(Parser)]
+pub enum TagCli {
+    /// Triage the filtered tags
+    Update(Box<TagUpdate>),
+}
+
+#[derive(Parser)]
+pub struct TagUpdate {
+    #[clap(flatten)]
+    pub triage: Triage,
+    #[clap(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(Parser)]
+pub enum ViewCli {
+    Save(SaveView),
+    List,
+    Remove { name: String },
+}
+
+#[derive(Parser)]
+#[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+pub struct SaveView {
+    pub name: String,
+    #[clap(
+        allow_hyphen_values = true,
+        multiple_values = true,
+        help = "Filter flags, e.g. --sc 200 --asset api"
+    )]
+    pub args: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct Normalize {
+    #[clap(
+        arg_enum,
+        ignore_case = true,
+        help = "Keep the current policy if empty"
+    )]
+    pub policy: Option<Normalization>,
+    #[clap(flatten)]
+    pub filter: Filter,
+}
+
+#[derive(Parser)]
+pub struct Resolve {
+    #[clap(
+        long,
+        help = "One resolver per line, e.g. 1.1.1.1, 9.9.9.9:53 or a resolv.conf"
+    )]
+    pub resolvers: Option<PathBuf>,
+    #[clap(
+        long,
+        default_value = "100",
+        help = "Most queries per second, 0 is unlimited"
+    )]
+    pub rate: u32,
+    #[clap(long, default_value = "50", help = "Names resolved at the same time")]
+    pub concurrency: usize,
+    #[clap(long, default_value = "2000", help = "Milliseconds")]
+    pub timeout: u64,
+    #[clap(long, default_value = "2")]
+    pub retries: usize,
+    #[clap(
+        long,
+        help = "Resolve names of stdin and print the ones that exist, e.g. luna dnsgen | luna resolve --stdin"
+    )]
+    pub stdin: bool,
+    #[clap(
+        long,
+        requires = "stdin",
+        help = "Insert the names of stdin that exist"
+    )]
+    pub insert: bool,
     #[clap(flatten)]
     pub filter: Filter,
 }
@@ -123,6 +324,9 @@ pub enum Server {
     Status,
 }
 
+// Inserted candidates of dnsgen, so they aren't all kept in memory
+const INSERT_CHUNK: usize = 4096;
+
 const BANNER: &str = r"
    __  __  ___  _____ 
   / / / / / / |/ / _ |  
@@ -142,7 +346,7 @@ pub fn run() {
 
     debug!("Running...");
 
-    let opt = Opt::parse();
+    let mut opt = Opt::parse();
 
     rayon::ThreadPoolBuilder::new()
         .num_threads(opt.threads.unwrap_or_default())
@@ -155,6 +359,22 @@ pub fn run() {
     let mut luna = Luna::parse(&opt.input);
     let output = opt.output.as_ref().unwrap_or(&opt.input);
 
+    let filter = match &mut opt.cli {
+        Cli::Remove(find) | Cli::Find(find) => Some(&mut find.filter),
+        Cli::Script(script) => Some(&mut script.filter),
+        Cli::Dnsgen(dg) => Some(&mut dg.filter),
+        Cli::Normalize(n) => Some(&mut n.filter),
+        Cli::Resolve(r) => Some(&mut r.filter),
+        Cli::Tag(TagCli::Update(u)) => Some(&mut u.filter),
+        _ => None,
+    };
+    if let Some(filter) = filter {
+        if let Err(err) = luna.resolve(filter) {
+            error!("{err}");
+            return;
+        }
+    }
+
     match opt.cli {
         Cli::Insert(insert) => {
             let res = match *insert {
@@ -174,10 +394,44 @@ pub fn run() {
             luna.save(output, !opt.no_backup);
         }
 
+        Cli::Find(find) if find.group.is_some() => {
+            let by = find.group.unwrap();
+            for (key, paths) in luna.group(by, &find.filter, find.verbose.max(1)) {
+                println!("{} ({})", key, paths.len());
+                paths.iter().for_each(|p| println!("    {}", p));
+            }
+        }
+
+        Cli::Find(find) if find.count => {
+            let values = luna.values(&find.filter);
+            let width = values.first().map_or(0, |v| v.count.to_string().len());
+            for value in values {
+                match find.verbose {
+                    0 => println!("{:>width$} {}", value.count, value.value, width = width),
+                    v => println!("{}", value.stringify(v + 1)),
+                }
+            }
+        }
+
         Cli::Find(find) => {
-            luna.find(find.field, &find.filter, find.verbose)
-                .iter()
-                .for_each(|r| println!("{}", r));
+            let lines = match (&find.template, &find.fields, find.format) {
+                (Some(template), _, _) => {
+                    template.render(&luna.table(find.field, &find.filter, &template.fields))
+                }
+                (None, None, None) => luna.find(find.field, &find.filter, find.verbose),
+                (None, None, Some(format)) if matches!(find.field, Field::Luna) => {
+                    luna.stats(&StatOptions::default()).render(format)
+                }
+                (None, fields, format) => {
+                    let fields = fields.clone().unwrap_or_else(|| Fields::of(find.field));
+                    format.unwrap_or(Format::Text).render(&luna.table(
+                        find.field,
+                        &find.filter,
+                        &fields,
+                    ))
+                }
+            };
+            lines.iter().for_each(|l| println!("{}", l));
         }
 
         Cli::Script(script) => match script.parse() {
@@ -207,6 +461,15 @@ pub fn run() {
                         luna.stringify(1),
                         input.display()
                     );
+                    if check.conflicts {
+                        let conflicts = luna.conflicts();
+                        if conflicts.is_empty() {
+                            println!("{} No conflicts", "[+]".green());
+                        }
+                        for conflict in conflicts {
+                            println!("{} Conflict: {}", "[-]".red(), conflict);
+                        }
+                    }
                 }
                 Err(err) => println!("{} Error in loading luna: {}", "[-]".red(), err),
             }
@@ -229,22 +492,313 @@ pub fn run() {
                 println!("[ ] No script file detected!")
             }
         }
-        Cli::Stat(s) => println!("{}", luna.stringify(s.verbose + 2)),
+        Cli::Stat(s) => {
+            let breakdown = s.opt.by.is_some() || s.opt.growth || s.opt.top > 0;
+            match s.format {
+                Some(format) => luna
+                    .stats(&s.opt)
+                    .render(format)
+                    .iter()
+                    .for_each(|l| println!("{}", l)),
+                None if breakdown => luna
+                    .stats(&s.opt)
+                    .render(Format::Text)
+                    .iter()
+                    .for_each(|l| println!("{}", l)),
+                None => println!("{}", luna.stringify(s.verbose + 2)),
+            }
+        }
         Cli::Dnsgen(dg) => {
-            let wl = if let Some(path) = dg.wl {
+            let mut wl: Vec<String> = if let Some(path) = &dg.wl {
                 std::fs::read_to_string(path)
                     .unwrap()
                     .split_ascii_whitespace()
                     .map(String::from)
                     .collect()
+            } else if dg.learn || dg.envs || dg.regions {
+                vec![]
             } else {
                 vec!["dev".to_string(), "test".to_string()]
             };
+            if dg.envs {
+                wl.extend(ENVS.iter().map(|w| w.to_string()));
+            }
+            if dg.regions {
+                wl.extend(REGIONS.iter().map(|w| w.to_string()));
+            }
+
+            let strategy = Strategy {
+                words: wl.clone(),
+                separators: dg.separators.clone(),
+                replace: dg.ops.contains(&Permutation::Replace),
+                prepend: dg.ops.contains(&Permutation::Prepend),
+                append: dg.ops.contains(&Permutation::Append),
+                insert: dg.ops.contains(&Permutation::Insert),
+                level: dg.ops.contains(&Permutation::Level),
+                range: dg.range,
+            };
 
-            dnsgen(luna.find(Field::Sub, &dg.filter, 0), wl)
-                .into_iter()
-                .for_each(|s| println!("{s}"))
+            let batches = if dg.per_program {
+                luna.find_by_program(Field::Sub, &dg.filter)
+                    .into_iter()
+                    .map(|(_, subs)| subs)
+                    .collect()
+            } else {
+                vec![luna.find(Field::Sub, &dg.filter, 0)]
+            };
+            let limit = dg.limit.unwrap_or(usize::MAX);
+
+            if let Some(dir) = &dg.out_dir {
+                if let Err(err) = fs::create_dir_all(dir) {
+                    error!("{err}");
+                    return;
+                }
+            }
+
+            let stdout = io::stdout();
+            let mut stdout = BufWriter::new(stdout.lock());
+            let mut files: HashMap<String, BufWriter<File>> = HashMap::new();
+            let mut inserts = Vec::with_capacity(INSERT_CHUNK);
+            let mut inserted = 0;
+
+            'batches: for subs in batches {
+                let (domains, errors) = roots(subs);
+                errors.iter().for_each(|err| warn!("{err}"));
+
+                // Learned from the whole batch, then written by root domain
+                type Candidates<'a> = Box<dyn Iterator<Item = String> + 'a>;
+                let groups: Box<dyn Iterator<Item = (String, Candidates)>> = if dg.learn {
+                    let names = domains.into_iter().flat_map(|(_, names)| names).collect();
+                    let (groups, _) = roots(learn(names, wl.clone(), limit));
+                    Box::new(
+                        groups
+                            .into_iter()
+                            .map(|(root, c)| (root, Box::new(c.into_iter()) as Candidates)),
+                    )
+                } else {
+                    Box::new(domains.into_iter().map(|(root, names)| {
+                        (root, Box::new(stream(names, &strategy)) as Candidates)
+                    }))
+                };
+                let mut left = limit;
+
+                for (root, candidates) in groups {
+                    if left == 0 {
+                        break;
+                    }
+
+                    let out: &mut dyn Write = match &dg.out_dir {
+                        Some(dir) => match files.entry(root) {
+                            Entry::Occupied(file) => file.into_mut(),
+                            Entry::Vacant(file) => {
+                                let path = dir.join(format!("{}.txt", file.key()));
+                                match File::create(&path) {
+                                    Ok(f) => file.insert(BufWriter::new(f)),
+                                    Err(err) => {
+                                        error!("{}: {err}", path.display());
+                                        continue;
+                                    }
+                                }
+                            }
+                        },
+                        None => &mut stdout,
+                    };
+
+                    for candidate in candidates.take(left) {
+                        left -= 1;
+                        if let Err(err) = writeln!(out, "{candidate}") {
+                            error!("{err}");
+                            break 'batches;
+                        }
+                        if dg.insert {
+                            inserts.push(candidate);
+                            if inserts.len() == INSERT_CHUNK {
+                                inserted += luna.insert_unverified(&inserts, "dnsgen");
+                                inserts.clear();
+                            }
+                        }
+                    }
+                }
+            }
+
+            for out in files.values_mut() {
+                if let Err(err) = out.flush() {
+                    error!("{err}");
+                }
+            }
+            if let Err(err) = stdout.flush() {
+                error!("{err}");
+            }
+
+            if dg.insert {
+                inserted += luna.insert_unverified(&inserts, "dnsgen");
+                info!("{inserted} candidates inserted.");
+                luna.save(output, !opt.no_backup);
+            }
         }
+        Cli::Resolve(r) => {
+            let mut config = match &r.resolvers {
+                Some(path) => match resolver::Config::from_file(path) {
+                    Ok(config) => config,
+                    Err(err) => {
+                        error!("{err}");
+                        return;
+                    }
+                },
+                None => resolver::Config::default(),
+            };
+            config.rate = r.rate;
+            config.concurrency = r.concurrency;
+            config.timeout = Duration::from_millis(r.timeout);
+            config.retries = r.retries;
+
+            let names: Vec<String> = if r.stdin {
+                io::stdin()
+                    .lines()
+                    .map_while(Result::ok)
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            } else {
+                luna.find(Field::Sub, &r.filter, 0)
+            };
+
+            let resolution = resolver::resolve(names, config);
+            for (domain, records) in resolution.wildcards {
+                let fingerprint = Dns {
+                    a: records.a,
+                    aaaa: records.aaaa,
+                    cname: records.cname,
+                    wildcard: true,
+                    time: Time::default(),
+                };
+                if let Ok(domain) = AssetName::from_str(&domain) {
+                    luna.insert_wildcard(&domain, fingerprint);
+                }
+            }
+
+            let (mut exist, mut failed) = (0, 0);
+            for res in resolution.names {
+                let res = match res {
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!("{err}");
+                        failed += 1;
+                        continue;
+                    }
+                };
+
+                let dns = Dns {
+                    a: res.records.a,
+                    aaaa: res.records.aaaa,
+                    cname: res.records.cname,
+                    wildcard: res.wildcard,
+                    time: Time::default(),
+                };
+                debug!("{}: {}", res.name, dns);
+                let verified = dns.exists() && !dns.wildcard;
+                if verified {
+                    exist += 1;
+                }
+
+                if r.stdin {
+                    if !verified {
+                        continue;
+                    }
+                    println!("{}", res.name);
+                    if !r.insert {
+                        continue;
+                    }
+                }
+
+                match AssetName::from_str(&res.name) {
+                    Ok(name) => {
+                        if let Err(err) = luna.insert_dns(name, dns) {
+                            warn!("{err}");
+                        }
+                    }
+                    Err(err) => warn!("{}: {err}", res.name),
+                }
+            }
+            info!("{exist} names exist, {failed} failed.");
+
+            if !r.stdin || r.insert {
+                luna.save(output, !opt.no_backup);
+            }
+        }
+        Cli::Normalize(n) => {
+            let merged = luna.normalize(n.policy, &n.filter);
+            info!("{merged} urls merged.");
+            luna.save(output, !opt.no_backup);
+        }
+        Cli::Program(ProgramCli::Import(import)) => {
+            let programs = fs::read_to_string(&import.file)
+                .map_err(|err| err.into())
+                .and_then(|text| bounty_targets(&text, import.platform.as_deref()));
+            match programs {
+                Ok(programs) => {
+                    let mut imported = 0;
+                    for program in programs
+                        .into_iter()
+                        .filter(|p| !import.bounty_only || p.typ.as_deref() == Some("bounty"))
+                    {
+                        match luna.insert_program(program) {
+                            Ok(_) => imported += 1,
+                            Err(err) => warn!("{err}"),
+                        }
+                    }
+                    info!("{imported} programs imported.");
+                    luna.save(output, !opt.no_backup);
+                }
+                Err(err) => error!("Can't import: {}", err),
+            }
+        }
+        Cli::Program(ProgramCli::Rename { name, to }) => match luna.rename_program(&name, &to) {
+            Ok(_) => luna.save(output, !opt.no_backup),
+            Err(err) => error!("{err}"),
+        },
+        Cli::Program(ProgramCli::Merge { name, into }) => match luna.merge_programs(&name, &into) {
+            Ok(_) => luna.save(output, !opt.no_backup),
+            Err(err) => error!("{err}"),
+        },
+        Cli::Program(ProgramCli::MoveAsset { asset, to }) => match luna.move_asset(&asset, &to) {
+            Ok(moved) => {
+                info!("{moved} assets moved.");
+                luna.save(output, !opt.no_backup)
+            }
+            Err(err) => error!("{err}"),
+        },
+        Cli::Tag(TagCli::Update(u)) => {
+            if u.triage.is_empty() {
+                error!("Nothing to update, see --help");
+                return;
+            }
+            let updated = luna.triage(&u.filter, &u.triage);
+            info!("{updated} tags updated.");
+            luna.save(output, !opt.no_backup);
+        }
+        Cli::View(view) => match view {
+            ViewCli::Save(v) => {
+                match luna.save_view(View {
+                    name: v.name,
+                    args: v.args,
+                }) {
+                    Ok(_) => luna.save(output, !opt.no_backup),
+                    Err(err) => error!("{err}"),
+                }
+            }
+            ViewCli::List => luna
+                .views
+                .iter()
+                .for_each(|v| println!("{}: {}", v.name, v.args.join(" "))),
+            ViewCli::Remove { name } => {
+                if luna.remove_view(&name) {
+                    luna.save(output, !opt.no_backup);
+                } else {
+                    warn!("No such view: {name}");
+                }
+            }
+        },
         Cli::Report(_) => todo!(),
         Cli::Server(_) => todo!(),
     }
@@ -0,0 +1,71 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// 256 MiB
+const MAX_BITS: u64 = 1 << 31;
+
+/// Set without false negatives, its false positive rate grows past the expected items
+pub struct Bloom {
+    bits: Vec<u64>,
+    len: u64,
+    hashes: u64,
+}
+
+impl Bloom {
+    pub fn new(items: usize, rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+
+        let len = (-items * rate.ln() / (ln2 * ln2))
+            .ceil()
+            .clamp(64.0, MAX_BITS as f64) as u64;
+        let hashes = (len as f64 / items * ln2).round().clamp(1.0, 16.0) as u64;
+
+        Self {
+            bits: vec![0; len.div_ceil(64) as usize],
+            len,
+            hashes,
+        }
+    }
+
+    fn hash(item: &str, seed: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// False if the item probably was already inserted
+    pub fn insert(&mut self, item: &str) -> bool {
+        let h1 = Self::hash(item, 0);
+        let h2 = Self::hash(item, 1) | 1;
+        let mut new = false;
+
+        for k in 0..self.hashes {
+            let bit = h1.wrapping_add(k.wrapping_mul(h2)) % self.len;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                new = true;
+            }
+        }
+
+        new
+    }
+}
+
+mod test {
+    #[test]
+    fn bloom() {
+        use super::*;
+
+        let mut bloom = Bloom::new(1000, 0.001);
+        assert!(bloom.insert("a.test.com"));
+        assert!(!bloom.insert("a.test.com"));
+
+        let new = (0..1000)
+            .filter(|i| bloom.insert(&format!("{i}.test.com")))
+            .count();
+        assert!(new > 990);
+    }
+}
@@ -0,0 +1,223 @@
+use addr::parse_domain_name;
+use std::collections::{HashMap, HashSet};
+
+// Most frequent tokens used for substitutions and insertions
+const TOKENS: usize = 64;
+
+// api-v2-staging.eu -> [api, v2, staging, eu] [-, -, .]
+fn split(sub: &str) -> (Vec<String>, Vec<char>) {
+    let mut tokens = vec![String::new()];
+    let mut seps = vec![];
+
+    for c in sub.chars() {
+        if matches!(c, '.' | '-' | '_') {
+            seps.push(c);
+            tokens.push(String::new());
+        } else if let Some(token) = tokens.last_mut() {
+            token.push(c);
+        }
+    }
+
+    (tokens, seps)
+}
+
+fn join(tokens: &[String], seps: &[char], root: &str) -> String {
+    let mut name = tokens[0].clone();
+    for (sep, token) in seps.iter().zip(&tokens[1..]) {
+        name.push(*sep);
+        name.push_str(token);
+    }
+    format!("{}.{}", name, root)
+}
+
+// v2 -> v1 v3, 009 -> 008 010
+fn bump(token: &str) -> Vec<String> {
+    let end = match token.rfind(|c: char| c.is_ascii_digit()) {
+        Some(i) => i + 1,
+        None => return vec![],
+    };
+    let start = token[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    let width = end - start;
+
+    match token[start..end].parse::<u64>() {
+        Ok(n) => [n.checked_sub(1), n.checked_add(1)]
+            .into_iter()
+            .flatten()
+            .map(|n| format!("{}{:0width$}{}", &token[..start], n, &token[end..]))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+struct Name {
+    tokens: Vec<String>,
+    seps: Vec<char>,
+    root: String,
+}
+
+/// Candidates made of tokens and patterns of the subdomains, the most frequent first
+pub fn learn(subdomains: Vec<String>, wl: Vec<String>, limit: usize) -> Vec<String> {
+    let existing: HashSet<String> = subdomains.iter().map(|s| s.to_lowercase()).collect();
+
+    let mut names = vec![];
+    let mut tokens: HashMap<String, usize> = HashMap::new();
+    let mut patterns: HashMap<String, usize> = HashMap::new();
+    let mut slots: HashMap<(String, usize, String), usize> = HashMap::new();
+
+    for subdomain in &existing {
+        let root = match parse_domain_name(subdomain)
+            .ok()
+            .and_then(|d| d.root().map(String::from))
+        {
+            Some(root) => root,
+            None => continue,
+        };
+        let sub = match subdomain
+            .strip_suffix(&root)
+            .and_then(|s| s.strip_suffix('.'))
+        {
+            Some(sub) if !sub.is_empty() => sub,
+            _ => continue,
+        };
+
+        let (toks, seps) = split(sub);
+        let pattern: String = seps.iter().collect();
+        *patterns.entry(pattern.clone()).or_default() += 1;
+        for (i, token) in toks.iter().enumerate().filter(|(_, t)| !t.is_empty()) {
+            *tokens.entry(token.clone()).or_default() += 1;
+            *slots
+                .entry((pattern.clone(), i, token.clone()))
+                .or_default() += 1;
+        }
+
+        names.push(Name {
+            tokens: toks,
+            seps,
+            root,
+        });
+    }
+
+    for word in wl {
+        tokens.entry(word.to_lowercase()).or_insert(1);
+    }
+
+    let mut top: Vec<(String, usize)> = tokens.iter().map(|(t, f)| (t.clone(), *f)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(TOKENS);
+
+    let mut scores: HashMap<String, usize> = HashMap::new();
+    let mut add = |candidate: String, score: usize| {
+        if !existing.contains(&candidate) {
+            *scores.entry(candidate).or_default() += score;
+        }
+    };
+
+    for name in &names {
+        let pattern: String = name.seps.iter().collect();
+        let weight = patterns[&pattern];
+
+        for i in 0..name.tokens.len() {
+            let current = &name.tokens[i];
+
+            // Same pattern with another token in one slot
+            for (token, freq) in top.iter().filter(|(t, _)| t != current) {
+                let slot = slots
+                    .get(&(pattern.clone(), i, token.clone()))
+                    .copied()
+                    .unwrap_or(0);
+                let mut toks = name.tokens.clone();
+                toks[i] = token.clone();
+                add(
+                    join(&toks, &name.seps, &name.root),
+                    freq + 2 * slot + weight,
+                );
+            }
+
+            for token in bump(current) {
+                let mut toks = name.tokens.clone();
+                toks[i] = token;
+                add(
+                    join(&toks, &name.seps, &name.root),
+                    tokens.get(current).copied().unwrap_or(0) + weight,
+                );
+            }
+        }
+
+        // New tokens before the name and at the end of its first label
+        let first = name
+            .seps
+            .iter()
+            .position(|&c| c == '.')
+            .unwrap_or(name.seps.len());
+        for (token, freq) in top.iter().filter(|(t, _)| !name.tokens.contains(t)) {
+            let sub = join(&name.tokens, &name.seps, &name.root);
+            add(format!("{}.{}", token, sub), *freq);
+            add(format!("{}-{}", token, sub), *freq);
+
+            let mut toks = name.tokens.clone();
+            let mut seps = name.seps.clone();
+            toks.insert(first + 1, token.clone());
+            seps.insert(first, '-');
+            add(join(&toks, &seps, &name.root), *freq);
+        }
+    }
+
+    let mut results: Vec<(String, usize)> = scores.into_iter().collect();
+    results.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    results.into_iter().take(limit).map(|(s, _)| s).collect()
+}
+
+mod test {
+    #[test]
+    fn split() {
+        use super::*;
+
+        let (tokens, seps) = split("api-v2-staging.eu");
+        assert_eq!(tokens, vec!["api", "v2", "staging", "eu"]);
+        assert_eq!(seps, vec!['-', '-', '.']);
+        assert_eq!(
+            join(&tokens, &seps, "test.com"),
+            "api-v2-staging.eu.test.com"
+        );
+    }
+
+    #[test]
+    fn bump() {
+        use super::*;
+
+        assert_eq!(bump("v2"), vec!["v1", "v3"]);
+        assert_eq!(bump("db009a"), vec!["db008a", "db010a"]);
+        assert_eq!(bump("x0"), vec!["x1"]);
+        assert!(bump("api").is_empty());
+    }
+
+    #[test]
+    fn learn() {
+        use super::*;
+
+        let subs = vec![
+            "api-v2-staging.test.com".to_string(),
+            "web-v1-prod.test.com".to_string(),
+            "api.test.com".to_string(),
+        ];
+
+        let results = learn(subs.clone(), vec![], usize::MAX);
+        assert!(results.contains(&"api-v2-prod.test.com".to_string()));
+        assert!(results.contains(&"api-v3-staging.test.com".to_string()));
+        assert!(results.contains(&"web.test.com".to_string()));
+        assert!(results.contains(&"staging.api.test.com".to_string()));
+        assert!(!results.iter().any(|r| subs.contains(r)));
+
+        // Ranked by frequency
+        let top = learn(subs.clone(), vec![], 5);
+        assert_eq!(top.len(), 5);
+        assert_eq!(top, results[..5]);
+
+        // Words of the wordlist are the least frequent tokens
+        let results = learn(subs, vec!["dev".to_string()], usize::MAX);
+        let dev = results.iter().position(|r| r == "dev.api.test.com");
+        assert!(dev.is_some_and(|i| i > results.len() / 2));
+    }
+}
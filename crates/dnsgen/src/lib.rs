@@ -1,55 +1,187 @@
 use addr::parse_domain_name;
 use std::fmt::{self, Display};
 
-pub fn dnsgen(subdomains: Vec<String>, wl: Vec<String>) -> Vec<String> {
-    let mut results = vec![];
+mod bloom;
+mod learn;
+mod strategy;
+use bloom::Bloom;
+pub use learn::learn;
+pub use strategy::{Strategy, ENVS, REGIONS};
 
-    for subdomain in subdomains.clone() {
-        let domain = if let Ok(d) = parse_domain_name(&subdomain) {
-            d
-        } else {
-            return vec![];
-        };
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub name: String,
+    pub reason: &'static str,
+}
 
-        let domain = if let Some(d) = domain.root() {
-            d
-        } else {
-            return vec![];
-        };
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.reason)
+    }
+}
+
+/// Names grouped by their root domain in order of appearance, and the invalid ones
+pub fn roots(names: Vec<String>) -> (Vec<(String, Vec<String>)>, Vec<Error>) {
+    let mut groups: Vec<(String, Vec<String>)> = vec![];
+    let mut errors = vec![];
 
-        let sub = subdomain.replace(domain, "");
+    for name in names {
+        let root = match parse_domain_name(&name) {
+            Ok(d) => d.root().map(String::from),
+            Err(_) => {
+                errors.push(Error {
+                    name,
+                    reason: "Invalid domain name",
+                });
+                continue;
+            }
+        };
 
-        if sub.is_empty() {
-            return vec![];
+        match root {
+            Some(root) => match groups.iter_mut().find(|(r, _)| *r == root) {
+                Some((_, names)) => names.push(name),
+                None => groups.push((root, vec![name])),
+            },
+            None => errors.push(Error {
+                name,
+                reason: "No root domain",
+            }),
         }
+    }
+
+    (groups, errors)
+}
+
+/// Invalid names and root domains are skipped
+pub fn dnsgen(subdomains: Vec<String>, wl: Vec<String>) -> Vec<String> {
+    let strategy = Strategy {
+        words: wl,
+        ..Default::default()
+    };
+    let mut results: Vec<String> = stream(subdomains, &strategy).collect();
+    results.sort();
+    results
+}
 
-        let subs = Sub::parse(sub).build(wl.clone());
+/// Candidates of [`dnsgen`] generated one by one in order of the subdomains,
+/// the existing and repeated ones are skipped by a bloom filter
+pub fn stream(subdomains: Vec<String>, strategy: &Strategy) -> Stream<'_> {
+    let mut names = vec![];
+    let mut items = subdomains.len();
 
-        let subs = subs
-            .into_iter()
-            .map(|mut p| {
-                p.push_str(domain);
-                p
-            })
-            .collect::<Vec<_>>();
+    for (domain, subdomains) in roots(subdomains.clone()).0 {
+        for subdomain in subdomains {
+            let sub = subdomain.replace(&domain, "");
 
-        results.extend(subs);
+            if sub.is_empty() {
+                continue;
+            }
+
+            let sub = Sub::parse(sub);
+            items = sub
+                .segments
+                .iter()
+                .fold(items, |n, s| n.saturating_add(strategy.len(s)));
+            names.push((domain.clone(), sub));
+        }
     }
 
-    results.sort();
-    results.dedup();
+    let mut seen = Bloom::new(items, RATE);
+    subdomains.iter().for_each(|s| {
+        seen.insert(s);
+    });
+
+    Stream {
+        strategy,
+        names: names.into_iter(),
+        current: None,
+        seen,
+    }
+}
+
+// False positive rate of the bloom filter, grows if it reaches its size limit
+const RATE: f64 = 0.0001;
+
+pub struct Stream<'a> {
+    strategy: &'a Strategy,
+    names: std::vec::IntoIter<(String, Sub)>,
+    current: Option<(String, Candidates<'a>)>,
+    seen: Bloom,
+}
 
-    for subdomain in subdomains {
-        if let Ok(i) = results.binary_search(&subdomain) {
-            results.swap_remove(i);
+impl Iterator for Stream<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (domain, candidates) = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    let (domain, sub) = self.names.next()?;
+                    self.current.insert((domain, sub.candidates(self.strategy)))
+                }
+            };
+
+            match candidates.next() {
+                Some(mut candidate) => {
+                    candidate.push_str(domain);
+                    if self.seen.insert(&candidate) {
+                        return Some(candidate);
+                    }
+                }
+                None => self.current = None,
+            }
         }
     }
+}
 
-    results
+// Every segment of sub replaced with numbers and words one by one
+struct Candidates<'a> {
+    sub: Sub,
+    strategy: &'a Strategy,
+    first: usize,
+    segment: usize,
+    // Numbers first, then each word
+    step: usize,
+    values: Box<dyn Iterator<Item = String>>,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.values.next() {
+                return Some(self.sub.with(self.segment, &value));
+            }
+
+            let segment = self.sub.segments.get(self.segment)?;
+            if self.step > self.strategy.words.len() {
+                self.segment += 1;
+                self.step = 0;
+                continue;
+            }
+
+            self.values = match (self.step, segment) {
+                (0, Segment::Num(s)) => self.strategy.numbers(s),
+                (0, _) => Box::new(std::iter::empty()),
+                (i, segment) => Box::new(
+                    self.strategy
+                        .values(
+                            self.segment == self.first,
+                            segment,
+                            &self.strategy.words[i - 1],
+                        )
+                        .into_iter(),
+                ),
+            };
+            self.step += 1;
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
-enum Segment {
+pub(crate) enum Segment {
     Alph(String),
     Num(String),
     Char(String),
@@ -103,57 +235,38 @@ impl Sub {
         Sub { segments }
     }
 
-    fn build(self, wl: Vec<String>) -> Vec<String> {
-        let mut subs = vec![];
-
-        for i in 0..self.segments.len() {
-            for w in wl.clone() {
-                match self.segments[i].clone() {
-                    Segment::Num(s) => {
-                        for j in 0..10u8 {
-                            let mut copy = self.segments.clone();
-                            let mut s = s.clone();
-                            s.replace_range(s.len() - 1..s.len(), &j.to_string());
-                            copy[i] = Segment::Alph(s);
-                            subs.push(copy.clone());
-                        }
-                        for pre in ["", "-", "."] {
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(format!("{}{}{}", w, pre, &s));
-                            subs.push(tmp);
-
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(format!("{}{}{}", &s, pre, w));
-                            subs.push(tmp);
-
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(w.to_string());
-                            subs.push(tmp);
-                        }
-                    }
-                    Segment::Alph(s) => {
-                        for pre in ["", "-", "."] {
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(format!("{}{}{}", w, pre, &s));
-                            subs.push(tmp);
-
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(format!("{}{}{}", &s, pre, w));
-                            subs.push(tmp);
-
-                            let mut tmp = self.segments.clone();
-                            tmp[i] = Segment::Alph(w.to_string());
-                            subs.push(tmp);
-                        }
+    fn candidates(self, strategy: &Strategy) -> Candidates<'_> {
+        let first = self
+            .segments
+            .iter()
+            .position(|s| !matches!(s, Segment::Char(_)))
+            .unwrap_or_default();
+
+        Candidates {
+            sub: self,
+            strategy,
+            first,
+            segment: 0,
+            step: 0,
+            values: Box::new(std::iter::empty()),
+        }
+    }
+
+    // String of the sub with the segment i replaced by value
+    fn with(&self, i: usize, value: &str) -> String {
+        let mut str = String::new();
+        for (j, s) in self.segments.iter().enumerate() {
+            match s {
+                _ if i == j => str.push_str(value),
+                Segment::Alph(s) | Segment::Num(s) => str.push_str(s),
+                Segment::Char(s) => {
+                    if !str.is_empty() {
+                        str.push_str(s)
                     }
-                    Segment::Char(_) => (),
                 }
             }
         }
-
-        subs.into_iter()
-            .map(|s| Sub { segments: s }.to_string())
-            .collect()
+        str
     }
 }
 
@@ -209,4 +322,69 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn roots() {
+        use super::*;
+
+        let (groups, errors) = roots(vec![
+            "a.google.com".to_string(),
+            "a.yahoo.com".to_string(),
+            "not a domain".to_string(),
+            "b.google.com".to_string(),
+            "com".to_string(),
+        ]);
+
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "google.com".to_string(),
+                    vec!["a.google.com".to_string(), "b.google.com".to_string()]
+                ),
+                ("yahoo.com".to_string(), vec!["a.yahoo.com".to_string()]),
+            ]
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].to_string(), "com: No root domain");
+    }
+
+    #[test]
+    fn dnsgen() {
+        use super::*;
+
+        let subs = vec![
+            "www1.google.com".to_string(),
+            "not a domain".to_string(),
+            "google.com".to_string(),
+        ];
+        let results = dnsgen(subs, vec!["dev".to_string()]);
+
+        assert!(results.contains(&"www2.google.com".to_string()));
+        assert!(results.contains(&"dev-www1.google.com".to_string()));
+        assert!(!results.contains(&"www1.google.com".to_string()));
+    }
+
+    #[test]
+    fn stream() {
+        use super::*;
+
+        let subs = vec!["www1.google.com".to_string(), "api.google.com".to_string()];
+        let wl = vec!["dev".to_string(), "api".to_string()];
+
+        let strategy = Strategy {
+            words: wl.clone(),
+            ..Default::default()
+        };
+        let results: Vec<String> = stream(subs.clone(), &strategy).collect();
+        let mut unique = results.clone();
+        unique.sort();
+        unique.dedup();
+
+        assert_eq!(results.len(), unique.len());
+        assert!(results.contains(&"www9.google.com".to_string()));
+        assert!(results.contains(&"api.www1.google.com".to_string()));
+        assert!(!results.iter().any(|r| subs.contains(r)));
+        assert_eq!(unique, dnsgen(subs, wl));
+    }
 }
@@ -0,0 +1,196 @@
+use crate::Segment;
+
+pub const ENVS: &[&str] = &[
+    "dev", "develop", "test", "qa", "uat", "stage", "staging", "preprod", "prod", "beta", "demo",
+    "sandbox", "int", "internal",
+];
+
+pub const REGIONS: &[&str] = &[
+    "us",
+    "eu",
+    "ap",
+    "sa",
+    "ca",
+    "uk",
+    "de",
+    "jp",
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-central-1",
+    "ap-south-1",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ap-northeast-1",
+    "sa-east-1",
+    "ca-central-1",
+];
+
+/// Words and the ways they are put in subdomains
+#[derive(Debug, Clone)]
+pub struct Strategy {
+    pub words: Vec<String>,
+    /// Joining words and segments
+    pub separators: Vec<String>,
+    /// dev: api -> dev
+    pub replace: bool,
+    /// dev: api -> dev-api
+    pub prepend: bool,
+    /// dev: api -> api-dev
+    pub append: bool,
+    /// dev: api-v2 -> api-dev-v2
+    pub insert: bool,
+    /// dev: api.v2 -> dev.api.v2, api.dev.v2, api.v2.dev
+    pub level: bool,
+    /// Numbers up to this far from each number, otherwise its last digit replaced by 0-9
+    pub range: Option<u64>,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            words: vec![],
+            separators: vec!["".to_string(), "-".to_string(), ".".to_string()],
+            replace: true,
+            prepend: true,
+            append: true,
+            insert: false,
+            level: false,
+            range: None,
+        }
+    }
+}
+
+impl Strategy {
+    // Generated one by one, so a large range isn't kept in memory
+    pub(crate) fn numbers(&self, s: &str) -> Box<dyn Iterator<Item = String>> {
+        let width = s.len();
+        match self.range {
+            None => {
+                let prefix = s[..width - 1].to_string();
+                Box::new((0..10).map(move |j| format!("{}{}", prefix, j)))
+            }
+            Some(range) => match s.parse::<u64>() {
+                Ok(n) => Box::new(
+                    (n.saturating_sub(range)..=n.saturating_add(range))
+                        .filter(move |&m| m != n)
+                        .map(move |m| format!("{:0width$}", m, width = width)),
+                ),
+                Err(_) => Box::new(std::iter::empty()),
+            },
+        }
+    }
+
+    pub(crate) fn values(&self, first: bool, segment: &Segment, w: &str) -> Vec<String> {
+        let mut values = vec![];
+
+        match segment {
+            Segment::Alph(s) | Segment::Num(s) => {
+                if self.replace {
+                    values.push(w.to_string());
+                }
+                for sep in &self.separators {
+                    if self.prepend {
+                        values.push(format!("{}{}{}", w, sep, s));
+                    }
+                    if self.append {
+                        values.push(format!("{}{}{}", s, sep, w));
+                    }
+                }
+                if self.level && first {
+                    values.push(format!("{}.{}", w, s));
+                }
+            }
+            Segment::Char(s) if s == "." => {
+                if self.level {
+                    values.push(format!(".{}.", w));
+                }
+            }
+            Segment::Char(s) if s.chars().count() == 1 => {
+                if self.insert {
+                    values.push(format!("{}{}{}", s, w, s));
+                }
+            }
+            Segment::Char(_) => (),
+        }
+
+        values
+    }
+
+    // Most values of a segment
+    pub(crate) fn len(&self, segment: &Segment) -> usize {
+        let words = self.words.len();
+        let values = words.saturating_mul(2 + 2 * self.separators.len());
+        match segment {
+            Segment::Num(_) => {
+                let numbers = self.range.map_or(10, |r| (r as usize).saturating_mul(2));
+                numbers.saturating_add(values)
+            }
+            Segment::Alph(_) => values,
+            Segment::Char(_) => words,
+        }
+    }
+}
+
+mod test {
+    #[test]
+    fn values() {
+        use super::*;
+
+        let strategy = Strategy {
+            separators: vec!["-".to_string()],
+            insert: true,
+            level: true,
+            ..Default::default()
+        };
+        let api = Segment::Alph("api".to_string());
+
+        assert_eq!(
+            strategy.values(true, &api, "dev"),
+            vec!["dev", "dev-api", "api-dev", "dev.api"]
+        );
+        assert_eq!(
+            strategy.values(false, &api, "dev"),
+            vec!["dev", "dev-api", "api-dev"]
+        );
+        assert_eq!(
+            strategy.values(false, &Segment::Char("-".to_string()), "dev"),
+            vec!["-dev-"]
+        );
+        assert_eq!(
+            strategy.values(false, &Segment::Char(".".to_string()), "dev"),
+            vec![".dev."]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        use super::*;
+
+        let strategy = Strategy::default();
+        let numbers = |strategy: &Strategy, s: &str| strategy.numbers(s).collect::<Vec<_>>();
+        assert_eq!(numbers(&strategy, "12").len(), 10);
+        assert_eq!(numbers(&strategy, "12")[0], "10");
+
+        let strategy = Strategy {
+            range: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(numbers(&strategy, "01"), vec!["00", "02", "03"]);
+        assert_eq!(numbers(&strategy, "10"), vec!["08", "09", "11", "12"]);
+
+        // Lazy and without overflows
+        let strategy = Strategy {
+            range: Some(u64::MAX),
+            ..Default::default()
+        };
+        assert_eq!(
+            strategy.numbers("5").take(3).collect::<Vec<_>>(),
+            vec!["0", "1", "2"]
+        );
+        assert_eq!(strategy.len(&Segment::Num("5".to_string())), usize::MAX);
+    }
+}
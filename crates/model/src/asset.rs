@@ -8,6 +8,16 @@ pub struct Asset {
     pub tags: Vec<Tag>,
     #[clap(skip)]
     pub start: Time,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<Time>,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Dns>,
+    // Records of random names, for domains
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wildcard: Option<Dns>,
 }
 
 impl FromStr for Asset {
@@ -18,13 +28,31 @@ impl FromStr for Asset {
             name: AssetName::from_str(s)?,
             tags: vec![],
             start: Time::default(),
+            update: None,
+            dns: None,
+            wildcard: None,
         })
     }
 }
 
 impl Asset {
+    // Last seen
+    pub fn seen(&self) -> Time {
+        self.update.unwrap_or(self.start)
+    }
+
     pub fn merge(&mut self, other: Self) {
+        let seen = self.seen().max(other.seen());
         self.start = self.start.min(other.start);
+        self.update = (seen > self.start).then_some(seen);
+
+        // The last resolutions
+        if other.dns.as_ref().map(|d| d.time) > self.dns.as_ref().map(|d| d.time) {
+            self.dns = other.dns;
+        }
+        if other.wildcard.as_ref().map(|d| d.time) > self.wildcard.as_ref().map(|d| d.time) {
+            self.wildcard = other.wildcard;
+        }
 
         for tag in other.tags {
             if let Some(self_tag) = self.tags.iter_mut().find(|t| t.name == tag.name) {
@@ -35,18 +63,17 @@ impl Asset {
         }
 
         if let (AssetName::Url(s), AssetName::Url(o)) = (&mut self.name, other.name) {
-            let mut s_q = s
-                .url
-                .query_pairs()
-                .filter(|(k, _)| !k.is_empty())
-                .collect::<HashMap<_, _>>();
-            let o_q = o
-                .url
-                .query_pairs()
-                .filter(|(k, _)| !k.is_empty())
-                .collect::<HashMap<_, _>>();
-
-            s_q.extend(o_q);
+            // Keys keep their first position and get the last value
+            let mut s_q: Vec<(_, _)> = vec![];
+            for (k, v) in s.url.query_pairs().chain(o.url.query_pairs()) {
+                if k.is_empty() {
+                    continue;
+                }
+                match s_q.iter_mut().find(|(key, _)| key == &k) {
+                    Some(pair) => pair.1 = v,
+                    None => s_q.push((k, v)),
+                }
+            }
 
             let mut query = String::new();
             for m in &s_q {
@@ -133,6 +160,7 @@ impl Asset {
             5 => format!(
                 "{}
     Tags:   [{}{}
+    DNS:    {}
     Start:  {}
     ",
                 self.name,
@@ -142,6 +170,7 @@ impl Asset {
                     .collect::<Vec<String>>()
                     .join(""),
                 if self.tags.is_empty() { "]" } else { "\n    ]" },
+                self.dns.as_ref().map_or("".to_string(), |d| d.to_string()),
                 self.start
                     .0
                     .with_timezone(&Local::now().timezone())
@@ -357,8 +386,8 @@ mod test {
         assert_eq!(
             assets,
             vec![
-                Asset::from_str("http://a.com/x/b").unwrap(),
                 Asset::from_str("http://a.com/a/x/y").unwrap(),
+                Asset::from_str("http://a.com/x/b").unwrap(),
                 Asset::from_str("http://a.com/x/y/z").unwrap(),
             ]
         );
@@ -372,18 +401,14 @@ mod test {
         assert_eq!(
             assets,
             vec![
-                Asset::from_str("http://a.com/x/y/z?j=jisoo&l=lisa").unwrap(),
                 Asset::from_str("http://a.com/x/y/z?l=lisa&j=jisoo&jen=jennie").unwrap(),
+                Asset::from_str("http://a.com/x/y/z?j=jisoo&l=lisa").unwrap(),
                 Asset::from_str("http://a.com/x/y/z?j=jisoo&l=lisa&r=rose").unwrap(),
             ]
         );
         assert_eq!(
-            assets,
-            vec![
-                Asset::from_str("http://a.com/x/y/z?j=jisoo&l=lisa").unwrap(),
-                Asset::from_str("http://a.com/x/y/z?j=jisoo&l=lisa&r=rose").unwrap(),
-                Asset::from_str("http://a.com/x/y/z?l=lisa&j=jisoo&jen=jennie").unwrap(),
-            ]
+            Asset::from_str("http://a.com/x/y/z?l=lisa&j=jisoo").unwrap(),
+            Asset::from_str("http://a.com/x/y/z?j=rose&l=rose").unwrap(),
         );
     }
 
@@ -0,0 +1,63 @@
+use super::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Records of the last resolution of a name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Dns {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub a: Vec<Ipv4Addr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aaaa: Vec<Ipv6Addr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cname: Vec<String>,
+    /// Answered like any name of its domain
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wildcard: bool,
+    pub time: Time,
+}
+
+impl Dns {
+    pub fn exists(&self) -> bool {
+        !(self.a.is_empty() && self.aaaa.is_empty() && self.cname.is_empty())
+    }
+
+    // Answered with nothing but the records of the wildcard fingerprint
+    pub fn within(&self, fingerprint: &Dns) -> bool {
+        self.exists()
+            && fingerprint.exists()
+            && self.a.iter().all(|ip| fingerprint.a.contains(ip))
+            && self.aaaa.iter().all(|ip| fingerprint.aaaa.contains(ip))
+            && self.cname.iter().all(|c| fingerprint.cname.contains(c))
+    }
+}
+
+/// What happens to subdomains answered by the wildcard of their domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnWildcard {
+    /// Inserted with the wildcard tag
+    #[default]
+    Tag,
+    /// Not inserted
+    Drop,
+    /// Inserted as they are
+    Keep,
+}
+
+impl Display for Dns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let records: Vec<String> = self
+            .cname
+            .iter()
+            .map(|c| format!("CNAME {}", c))
+            .chain(self.a.iter().map(|ip| format!("A {}", ip)))
+            .chain(self.aaaa.iter().map(|ip| format!("AAAA {}", ip)))
+            .collect();
+
+        match (records.is_empty(), self.wildcard) {
+            (true, _) => write!(f, "NXDOMAIN"),
+            (false, true) => write!(f, "{} (wildcard)", records.join(", ")),
+            (false, false) => write!(f, "{}", records.join(", ")),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use std::net::IpAddr;
 
 #[derive(Clone, ArgEnum, Copy)]
 pub enum Field {
@@ -31,10 +32,23 @@ impl Field {
     }
 }
 
+impl Field {
+    pub fn asset(&self, name: &AssetName) -> bool {
+        matches!(
+            (name, self),
+            (AssetName::Domain(_), Field::Domain)
+                | (AssetName::Subdomain(_), Field::Sub)
+                | (AssetName::Url(_), Field::Url)
+                | (AssetName::Cidr(_), Field::Cidr)
+                | (_, Field::Asset)
+        )
+    }
+}
+
 #[derive(Parser)]
 pub struct Filter {
-    #[clap(short, default_value = "18446744073709551615")]
-    pub n: usize,
+    #[clap(short, help = "Most results")]
+    pub n: Option<usize>,
 
     #[clap(long, short)]
     pub program: Option<Regex>,
@@ -46,8 +60,10 @@ pub struct Filter {
     pub url: Option<Regex>,
     #[clap(long)]
     pub handle: Option<Regex>,
-    #[clap(long)]
+    #[clap(long, help = "Any bounty range, e.g. critical")]
     pub bounty: Option<Regex>,
+    #[clap(long, help = "Pays at least this much for some severity")]
+    pub min_bounty: Option<u64>,
     #[clap(long)]
     pub state: Option<Regex>,
     #[clap(long, short)]
@@ -62,23 +78,69 @@ pub struct Filter {
     pub tag: Option<Regex>,
     #[clap(long = "sv")]
     pub severity: Option<Regex>,
+    #[clap(long = "min-sv", help = "At least this severity, e.g. medium")]
+    pub min_severity: Option<Severity>,
     #[clap(long)]
     pub value: Option<Regex>,
-
-    #[clap(long, short, name = "HOURS", help = "How many hours ago?")]
+    #[clap(
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        help = "Tags in any of these states, e.g. new,triaged"
+    )]
+    pub status: Vec<Status>,
+    #[clap(long)]
+    pub assignee: Option<Regex>,
+
+    #[clap(
+        long,
+        short,
+        visible_alias = "since",
+        name = "SINCE",
+        help = "First seen since, e.g. 48 (hours), 7d, 90m or 2026-10-01"
+    )]
     pub start: Option<Time>,
+    #[clap(
+        long,
+        parse(try_from_str = Time::end),
+        help = "First seen before, or on if a date"
+    )]
+    pub until: Option<Time>,
+    #[clap(long, help = "Last seen since")]
+    pub seen_since: Option<Time>,
+    #[clap(
+        long,
+        parse(try_from_str = Time::end),
+        help = "Last seen before, or on if a date"
+    )]
+    pub seen_until: Option<Time>,
+
+    #[clap(long, help = "e.g. sub ~ api and not tag = waf and sc in [200,302]")]
+    pub query: Option<Query>,
+
+    #[clap(long, arg_enum, ignore_case = true)]
+    pub sort: Option<Sort>,
+    #[clap(long, help = "Reverse the order")]
+    pub desc: bool,
+    #[clap(long, help = "Skip the first results")]
+    pub offset: Option<usize>,
+
+    #[clap(long, help = "Saved filter, the flags given here take precedence")]
+    pub view: Option<String>,
 }
 
 impl Default for Filter {
     fn default() -> Self {
         Self {
-            n: 18446744073709551615,
+            n: None,
             program: None,
             platform: None,
             typ: None,
             url: None,
             handle: None,
             bounty: None,
+            min_bounty: None,
             state: None,
             asset: None,
             sc: None,
@@ -86,40 +148,141 @@ impl Default for Filter {
             resp: None,
             tag: None,
             severity: None,
+            min_severity: None,
             value: None,
+            status: vec![],
+            assignee: None,
 
             start: Some(Time(Utc::now() - chrono::Duration::weeks(5400))),
+            until: None,
+            seen_since: None,
+            seen_until: None,
+            query: None,
+
+            sort: None,
+            desc: false,
+            offset: None,
+
+            view: None,
         }
     }
 }
 
+#[derive(Clone, ArgEnum, Copy)]
+pub enum Sort {
+    Start,
+    Name,
+    Sc,
+    Severity,
+}
+
+#[derive(Clone, ArgEnum, Copy)]
+pub enum Group {
+    Severity,
+    Program,
+}
+
+fn sc_cmp(a: &Option<String>, b: &Option<String>) -> Ordering {
+    let num = |s: &Option<String>| s.as_deref().and_then(|s| s.trim().parse::<u16>().ok());
+    num(a).cmp(&num(b)).then_with(|| a.cmp(b))
+}
+
+pub trait Sortable {
+    fn cmp_by(&self, other: &Self, sort: Sort) -> Ordering;
+}
+
+impl Sortable for Program {
+    fn cmp_by(&self, other: &Self, sort: Sort) -> Ordering {
+        fn severity(p: &Program) -> Option<&Severity> {
+            p.assets.iter().filter_map(|a| a.severity()).max()
+        }
+        match sort {
+            Sort::Start => self.start.cmp(&other.start),
+            Sort::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            Sort::Sc => Ordering::Equal,
+            Sort::Severity => severity(self).cmp(&severity(other)),
+        }
+    }
+}
+
+impl Sortable for Asset {
+    fn cmp_by(&self, other: &Self, sort: Sort) -> Ordering {
+        let sc = |a: &Asset| match &a.name {
+            AssetName::Url(req) => req.sc.clone(),
+            _ => None,
+        };
+        match sort {
+            Sort::Start => self.start.cmp(&other.start),
+            Sort::Name => self.name.to_string().cmp(&other.name.to_string()),
+            Sort::Sc => sc_cmp(&sc(self), &sc(other)),
+            Sort::Severity => self.severity().cmp(&other.severity()),
+        }
+    }
+}
+
+impl Asset {
+    // The most severe tag
+    fn severity(&self) -> Option<&Severity> {
+        self.tags.iter().filter_map(|t| t.severity.as_ref()).max()
+    }
+}
+
+impl Sortable for Tag {
+    fn cmp_by(&self, other: &Self, sort: Sort) -> Ordering {
+        match sort {
+            Sort::Start => self.start.cmp(&other.start),
+            Sort::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            Sort::Sc => Ordering::Equal,
+            Sort::Severity => self.severity.cmp(&other.severity),
+        }
+    }
+}
+
+// cidr:10.0.0.0/8 re:^api glob:*.dev.* exact:admin
+#[derive(Default)]
 pub enum Regex {
     Cidr(IpNet),
     Regex(regex::Regex),
+    Exact(String),
+    #[default]
     Empty,
 }
-impl Default for Regex {
-    fn default() -> Regex {
-        Regex::Empty
-    }
+
+fn ip(s: &str) -> Option<IpNet> {
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn glob(s: &str) -> String {
+    let pattern: String = s
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect();
+    format!("^{}$", pattern)
 }
 
 impl FromStr for Regex {
     type Err = Errors;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.is_empty() {
-            Ok(Self::Empty)
-        } else if s.contains('.') {
-            if let Ok(cidr) = s.parse::<IpNet>() {
-                Ok(Self::Cidr(cidr))
-            } else if let Ok(cidr) = format!("{}/32", s).parse::<IpNet>() {
-                Ok(Self::Cidr(cidr))
-            } else {
-                Ok(Self::Regex(regex::Regex::new(&format!("(?i){}", s))?))
-            }
-        } else {
+        let regex = |s: &str| -> Result<Self, Self::Err> {
             Ok(Self::Regex(regex::Regex::new(&format!("(?i){}", s))?))
+        };
+
+        match s.split_once(':') {
+            _ if s.is_empty() => Ok(Self::Empty),
+            Some(("cidr", c)) => ip(c)
+                .map(Self::Cidr)
+                .ok_or_else(|| format!("Invalid cidr: {c}").into()),
+            Some(("re", re)) => regex(re),
+            Some(("glob", g)) => regex(&glob(g)),
+            Some(("exact", e)) => Ok(Self::Exact(e.to_lowercase())),
+            _ => ip(s).map_or_else(|| regex(s), |c| Ok(Self::Cidr(c))),
         }
     }
 }
@@ -127,6 +290,7 @@ impl FromStr for Regex {
 trait RegexOpt {
     fn is_empty(&self) -> bool;
     fn cidr_match(&self, cidr: &IpNet) -> bool;
+    fn host_match(&self, host: &Host) -> bool;
     fn string_match(&self, str: &str) -> bool;
     fn option_match(&self, str: &Option<String>) -> bool;
 }
@@ -140,22 +304,28 @@ impl RegexOpt for Option<Regex> {
     }
 
     fn cidr_match(&self, cidr: &IpNet) -> bool {
-        if let Some(re) = self {
-            match re {
-                Regex::Cidr(fcidr) => fcidr.contains(cidr) || cidr.contains(fcidr),
-                Regex::Regex(_) => false,
-                Regex::Empty => true,
-            }
+        if let Some(Regex::Cidr(fcidr)) = self {
+            fcidr.contains(cidr) || cidr.contains(fcidr)
         } else {
-            true
+            self.string_match(&cidr.to_string())
+        }
+    }
+
+    // IP hosts are matched by CIDRs
+    fn host_match(&self, host: &Host) -> bool {
+        match host {
+            Host::Ipv4(ip) => self.cidr_match(&IpNet::from(IpAddr::from(*ip))),
+            Host::Ipv6(ip) => self.cidr_match(&IpNet::from(IpAddr::from(*ip))),
+            Host::Domain(d) => self.string_match(d),
         }
     }
 
     fn string_match(&self, str: &str) -> bool {
         if let Some(re) = self {
             match re {
-                Regex::Cidr(_) => false,
+                Regex::Cidr(fcidr) => ip(str).is_some_and(|c| fcidr.contains(&c)),
                 Regex::Regex(re) => re.is_match(str),
+                Regex::Exact(e) => e == &str.to_lowercase(),
                 Regex::Empty => true,
             }
         } else {
@@ -164,15 +334,9 @@ impl RegexOpt for Option<Regex> {
     }
 
     fn option_match(&self, str: &Option<String>) -> bool {
-        if let Some(re) = self {
-            match (re, str) {
-                (Regex::Cidr(_), _) => false,
-                (Regex::Regex(re), Some(str)) => re.is_match(str),
-                (Regex::Regex(_), None) => false,
-                _ => true,
-            }
-        } else {
-            true
+        match str {
+            Some(str) => self.string_match(str),
+            None => self.is_empty(),
         }
     }
 }
@@ -184,17 +348,48 @@ impl Filter {
             && self.typ.option_match(&program.typ)
             && self.url.option_match(&program.url)
             && self.handle.option_match(&program.handle)
-            && self.bounty.option_match(&program.bounty)
-            && self.state.option_match(&program.state)
+            && (self.bounty.is_empty()
+                || program
+                    .bounty
+                    .iter()
+                    .any(|r| self.bounty.string_match(&r.to_string())))
+            && self
+                .min_bounty
+                .is_none_or(|min| program.max_bounty().is_some_and(|max| max >= min))
+            && self
+                .state
+                .option_match(&program.state.as_ref().map(|s| s.as_str().to_string()))
             && (self.asset_is_empty() || program.assets.iter().any(|a| self.asset(a)))
+            && self.query.as_ref().is_none_or(|q| q.program(program))
+    }
+    // Query with program fields already evaluated
+    pub fn query(&self, program: &Program) -> Query {
+        self.query
+            .as_ref()
+            .map_or(Query::Const(true), |q| q.bind_program(program))
     }
     pub fn asset(&self, asset: &Asset) -> bool {
+        // Names are matched by CIDRs through their resolved addresses
+        let resolved = || {
+            matches!(self.asset, Some(Regex::Cidr(_)))
+                && asset.dns.as_ref().is_some_and(|dns| {
+                    dns.a
+                        .iter()
+                        .map(|ip| IpAddr::from(*ip))
+                        .chain(dns.aaaa.iter().map(|ip| IpAddr::from(*ip)))
+                        .any(|ip| self.asset.cidr_match(&IpNet::from(ip)))
+                })
+        };
         (match &asset.name {
             AssetName::Domain(d) => self.asset.string_match(d),
-            AssetName::Subdomain(h) => self.asset.string_match(&h.to_string()),
+            AssetName::Subdomain(h) => self.asset.host_match(h) || resolved(),
             AssetName::Url(req) => {
-                self.asset.string_match(req.url.as_str())
-                    && self.sc.option_match(&req.sc)
+                (match (&self.asset, req.url.host()) {
+                    (Some(Regex::Cidr(_)), Some(host)) => {
+                        self.asset.host_match(&host.to_owned()) || resolved()
+                    }
+                    _ => self.asset.string_match(req.url.as_str()),
+                }) && self.sc.option_match(&req.sc)
                     && self.title.option_match(&req.title)
                     && self.resp.option_match(&req.resp)
             }
@@ -203,13 +398,103 @@ impl Filter {
     }
     pub fn tag(&self, tag: &Tag) -> bool {
         self.tag.string_match(&tag.name)
-            && self.severity.option_match(&tag.severity)
-            && (self.value.is_empty() || tag.values.iter().any(|v| self.value.string_match(v)))
+            && self
+                .severity
+                .option_match(&tag.severity.as_ref().map(|s| s.to_string()))
+            && self
+                .min_severity
+                .as_ref()
+                .is_none_or(|min| tag.severity.as_ref().is_some_and(|s| s >= min))
+            && (self.value.is_empty()
+                || tag.values.iter().any(|v| self.value.string_match(&v.value)))
+            && (self.status.is_empty() || self.status.contains(&tag.status()))
+            && self.assignee.option_match(&tag.assignee)
+    }
+    // Add a saved filter, given flags take precedence and the others come from it
+    pub fn merge(&mut self, other: Filter) {
+        merge(&mut self.n, other.n, false);
+
+        merge(&mut self.program, other.program, false);
+        merge(&mut self.platform, other.platform, false);
+        merge(&mut self.typ, other.typ, false);
+        merge(&mut self.url, other.url, false);
+        merge(&mut self.handle, other.handle, false);
+        merge(&mut self.bounty, other.bounty, false);
+        merge(&mut self.min_bounty, other.min_bounty, false);
+        merge(&mut self.state, other.state, false);
+        merge(&mut self.asset, other.asset, false);
+        merge(&mut self.sc, other.sc, false);
+        merge(&mut self.title, other.title, false);
+        merge(&mut self.resp, other.resp, false);
+        merge(&mut self.tag, other.tag, false);
+        merge(&mut self.severity, other.severity, false);
+        merge(&mut self.min_severity, other.min_severity, false);
+        merge(&mut self.value, other.value, false);
+        if self.status.is_empty() {
+            self.status = other.status;
+        }
+        merge(&mut self.assignee, other.assignee, false);
+
+        merge(&mut self.start, other.start, false);
+        merge(&mut self.until, other.until, false);
+        merge(&mut self.seen_since, other.seen_since, false);
+        merge(&mut self.seen_until, other.seen_until, false);
+        merge(&mut self.query, other.query, false);
+
+        merge(&mut self.sort, other.sort, false);
+        // Only given as true
+        self.desc |= other.desc;
+        merge(&mut self.offset, other.offset, false);
+    }
+
+    // First seen and last seen ranges
+    pub fn time(&self, start: Time, seen: Time) -> bool {
+        self.start.is_none_or(|t| t <= start)
+            && self.until.is_none_or(|t| start < t)
+            && self.seen_since.is_none_or(|t| t <= seen)
+            && self.seen_until.is_none_or(|t| seen < t)
     }
     pub fn value(&self, str: &str) -> bool {
         self.value.string_match(str)
     }
 
+    // Sort, skip and truncate results, keeping the storage order of equal ones
+    pub fn page<T, S: Sortable + ?Sized>(
+        &self,
+        items: impl Iterator<Item = T>,
+        key: impl Fn(&T) -> &S,
+    ) -> Vec<T> {
+        if self.sort.is_none() && !self.desc {
+            return items.skip(self.offset()).take(self.n()).collect();
+        }
+
+        let mut items: Vec<T> = items.collect();
+        match self.sort {
+            Some(sort) => items.sort_by(|a, b| {
+                let ord = key(a).cmp_by(key(b), sort);
+                if self.desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            }),
+            None => items.reverse(),
+        }
+        items
+            .into_iter()
+            .skip(self.offset())
+            .take(self.n())
+            .collect()
+    }
+
+    pub fn n(&self) -> usize {
+        self.n.unwrap_or(usize::MAX)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or_default()
+    }
+
     pub fn asset_is_empty(&self) -> bool {
         self.asset.is_empty()
             && self.url.is_empty()
@@ -219,7 +504,12 @@ impl Filter {
             && self.tag_is_empty()
     }
     pub fn tag_is_empty(&self) -> bool {
-        self.tag.is_empty() && self.severity.is_empty() && self.value.is_empty()
+        self.tag.is_empty()
+            && self.severity.is_empty()
+            && self.min_severity.is_none()
+            && self.value.is_empty()
+            && self.status.is_empty()
+            && self.assignee.is_empty()
     }
 }
 
@@ -233,7 +523,7 @@ mod test {
 
         asset.tags.push(Tag {
             name: "sql".to_string(),
-            severity: Some("High".to_string()),
+            severity: Some(Severity::High),
             ..Default::default()
         });
 
@@ -256,6 +546,49 @@ mod test {
         assert!(!filter3.asset(&asset));
     }
 
+    #[test]
+    fn until() {
+        use super::*;
+
+        let f = Filter::try_parse_from(["filter", "--until", "2024-05-01"]).unwrap();
+        let t = |s: &str| Time::from_str(s).unwrap();
+        assert!(f.time(t("2024-05-01 23:59:59"), t("2024-05-01 23:59:59")));
+        assert!(!f.time(t("2024-05-02"), t("2024-05-02")));
+
+        let f = Filter::try_parse_from(["filter", "--seen-until", "2024-05-01 12:00"]).unwrap();
+        assert!(!f.time(t("2024-05-01"), t("2024-05-01 12:00")));
+    }
+
+    #[test]
+    fn cidr() {
+        use super::*;
+
+        let f = Filter {
+            asset: Some(Regex::from_str("10.0.0.0/24").unwrap()),
+            ..Default::default()
+        };
+        assert!(f.asset(&Asset::from_str("10.0.0.5").unwrap()));
+        assert!(f.asset(&Asset::from_str("http://10.0.0.5:8080/").unwrap()));
+
+        let dns = |ip: &str| Dns {
+            a: vec![ip.parse().unwrap()],
+            aaaa: vec![],
+            cname: vec![],
+            wildcard: false,
+            time: Time::default(),
+        };
+        let mut sub = Asset::from_str("api.google.com").unwrap();
+        assert!(!f.asset(&sub));
+        sub.dns = Some(dns("10.0.0.7"));
+        assert!(f.asset(&sub));
+        sub.dns = Some(dns("10.0.1.7"));
+        assert!(!f.asset(&sub));
+
+        let mut url = Asset::from_str("https://api.google.com/login").unwrap();
+        url.dns = Some(dns("10.0.0.7"));
+        assert!(f.asset(&url));
+    }
+
     #[test]
     fn asset_is_empty() {
         use super::*;
@@ -306,4 +639,34 @@ mod test {
         let regex = Some(Regex::from_str("1.1.1.1/32").unwrap());
         assert!(!regex.string_match(str))
     }
+
+    #[test]
+    fn typed() {
+        use super::*;
+
+        let re = |s: &str| Some(Regex::from_str(s).unwrap());
+        let filter = |s: &str| Filter {
+            asset: re(s),
+            ..Default::default()
+        };
+        let asset = |s: &str| Asset::from_str(s).unwrap();
+
+        assert!(filter("10.0.0.1").asset(&asset("http://10.0.0.1:8080/admin")));
+        assert!(filter("10.0.0.0/24").asset(&asset("10.0.0.7")));
+        assert!(!filter("10.0.0.0/24").asset(&asset("http://10.0.1.1/")));
+        assert!(filter("cidr:2001:db8::/32").asset(&asset("http://[2001:db8::1]/")));
+        assert!(filter("2001:db8::/32").asset(&asset("2001:db8:1::/48")));
+        assert!(filter("::1").asset(&asset("http://[::1]/")));
+        assert!(Regex::from_str("cidr:google").is_err());
+
+        assert!(filter("re:^api\\.").asset(&asset("api.google.com")));
+        assert!(!filter("re:^api\\.").asset(&asset("dev.api.google.com")));
+        assert!(filter("glob:*.dev.*").asset(&asset("a.dev.google.com")));
+        assert!(!filter("glob:*.dev.*").asset(&asset("dev.google.com")));
+        assert!(filter("exact:Google.com").asset(&asset("google.com")));
+        assert!(!filter("exact:google.com").asset(&asset("api.google.com")));
+
+        assert!(re("10.0.0.0/8").string_match("10.1.1.1"));
+        assert!(!re("10.0.0.0/8").option_match(&None));
+    }
 }
@@ -0,0 +1,36 @@
+use super::*;
+
+/// Positions of values by key, rebuilt after load and after removing values
+#[derive(Default, Clone)]
+pub struct Index(HashMap<String, Vec<usize>>);
+
+impl Index {
+    pub fn get(&self, key: &str) -> &[usize] {
+        self.0.get(key).map_or(&[], |v| v)
+    }
+
+    pub fn insert(&mut self, key: String, position: usize) {
+        let positions = self.0.entry(key).or_default();
+        if !positions.contains(&position) {
+            positions.push(position);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Index({} keys)", self.0.len())
+    }
+}
@@ -4,11 +4,9 @@ use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
 use ipnet::IpNet;
 use log::{debug, error, info, warn};
 use rayon::prelude::*;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
-use std::sync::Mutex;
 use std::{
     fmt::{self, Display},
     io::{BufRead, BufReader, Write},
@@ -16,29 +14,43 @@ use std::{
     process::{Command, Stdio},
     str::FromStr,
     sync::{
-        atomic::{self, AtomicBool},
-        Arc,
+        atomic::{self, AtomicBool, AtomicUsize},
+        mpsc, Arc,
     },
 };
 
 use url::Host;
 
 pub mod asset;
+pub mod dns;
 pub mod filter;
+pub mod index;
 pub mod luna;
+pub mod output;
+pub mod platform;
 pub mod program;
+pub mod query;
 pub mod request;
 pub mod script;
+pub mod severity;
+pub mod stats;
 pub mod tag;
 pub mod time;
 
 pub use asset::*;
+pub use dns::{Dns, OnWildcard};
 pub use filter::*;
-pub use luna::Luna;
-pub use program::Program;
-pub use request::Request;
+pub use index::Index;
+pub use luna::{Luna, View};
+pub use output::{Format, Table, Template};
+pub use platform::{bounty_targets, Reward, State};
+pub use program::{Aggregation, Conflict, OnConflict, Program};
+pub use query::{Fields, Query};
+pub use request::{Normalization, Request};
 pub use script::ScriptCli;
-pub use tag::Tag;
+pub use severity::Severity;
+pub use stats::{Breakdown, StatOptions, Stats};
+pub use tag::{Status, Tag, Triage, Value};
 pub use time::Time;
 
 pub type Errors = Box<dyn std::error::Error + Sync + Send>;
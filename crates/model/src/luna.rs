@@ -1,4 +1,5 @@
 use super::*;
+use std::net::IpAddr;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -11,6 +12,40 @@ pub struct Luna {
     pub programs: Vec<Program>,
 
     pub start: Time,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub views: Vec<View>,
+
+    // Asset keys to programs
+    #[serde(skip)]
+    pub index: Index,
+    // Lowercase names to programs
+    #[serde(skip)]
+    pub names: Index,
+}
+
+// Filter arguments saved by name
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct View {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl View {
+    pub fn filter(&self) -> Result<Filter, Errors> {
+        let filter = Filter::try_parse_from(
+            std::iter::once("view").chain(self.args.iter().map(|a| a.as_str())),
+        )?;
+        if filter.view.is_some() {
+            return Err(format!("View {} uses another view", self.name).into());
+        }
+        Ok(filter)
+    }
+}
+
+// google > https://api.google.com/v1 > sqli [high]
+fn path(program: &Program, asset: &Asset, tag: &Tag, v: u8) -> String {
+    format!("{} > {} > {}", program.name, asset.name, tag.stringify(v))
 }
 
 impl Default for Luna {
@@ -21,70 +56,423 @@ impl Default for Luna {
             status: "In the service of Selemene".to_string(),
             programs: vec![],
             start: Time::default(),
+            views: vec![],
+            index: Index::default(),
+            names: Index::default(),
         }
     }
 }
 
+// Urls are indexed by origin, each program has its own url policy
+fn key(name: &AssetName) -> String {
+    match name {
+        AssetName::Url(req) => req.url[..url::Position::BeforePath].to_string(),
+        name => name.to_string(),
+    }
+}
+
 impl Luna {
     pub fn merge(&mut self, other: Self) {
         self.start = self.start.min(other.start);
 
         for program in other.programs {
-            if let Some(self_program) = self.programs.iter_mut().find(|t| t.name == program.name) {
+            if let Some(self_program) = self
+                .programs
+                .iter_mut()
+                .find(|t| t.name.to_lowercase() == program.name.to_lowercase())
+            {
                 self_program.merge(program);
             } else {
                 self.programs.push(program);
             }
         }
+
+        for view in other.views {
+            if !self.views.iter().any(|v| v.name == view.name) {
+                self.views.push(view);
+            }
+        }
+
+        self.reindex();
+    }
+
+    // Must be called after changing `programs` directly
+    pub fn reindex(&mut self) {
+        self.index.clear();
+        self.names.clear();
+
+        for i in 0..self.programs.len() {
+            self.programs[i].reindex();
+            self.index_program(i, 0);
+        }
+    }
+
+    // Index the program and its assets from `from` onwards
+    fn index_program(&mut self, i: usize, from: usize) {
+        let program = &self.programs[i];
+
+        self.names.insert(program.name.to_lowercase(), i);
+        for asset in &program.assets[from..] {
+            self.index.insert(key(&asset.name), i);
+        }
     }
 
     pub fn insert_program(&mut self, mut program: Program) -> Result<(), Errors> {
-        for asset in std::mem::take(&mut program.assets) {
-            program.insert_asset(asset);
+        let own = self.program_position(&program.name);
+        let policy = program
+            .conflict
+            .or_else(|| own.and_then(|i| self.programs[i].conflict))
+            .unwrap_or_default();
+
+        let mut shared = vec![];
+        for asset in program.take_assets() {
+            let owners = self.owners(&asset.name);
+            // Assets the program already has stay in it
+            let owner = match owners.iter().any(|(j, _)| Some(*j) == own) {
+                true => None,
+                false => owners.into_iter().next(),
+            };
+            match (owner, policy) {
+                (Some((j, scope)), OnConflict::Shared) => {
+                    warn!(
+                        "{} belongs to {}, kept there",
+                        asset.name, self.programs[j].name
+                    );
+                    shared.push((j, scope, asset));
+                }
+                (Some((j, _)), OnConflict::Skip) => {
+                    warn!("{} belongs to {}", asset.name, self.programs[j].name)
+                }
+                _ => {
+                    program.insert_asset(asset);
+                }
+            }
         }
+        let name = program.name.clone();
 
-        if let Some(p) = self.program_by_name(&program.name) {
+        let i = if let Some(i) = self.program_position(&program.name) {
+            let p = &mut self.programs[i];
             p.merge(program);
             p.aggregate();
+            i
         } else {
             program.aggregate();
             self.programs.push(program);
-        }
+            self.programs.len() - 1
+        };
+        self.index_program(i, 0);
 
+        for (j, scope, asset) in shared {
+            self.share(j, &scope, asset, &name);
+        }
         Ok(())
     }
 
+    // Keep the asset in its program and tag its scope as shared with the other one
+    fn share(&mut self, i: usize, scope: &AssetName, asset: Asset, with: &str) {
+        self.insert_into(i, asset);
+
+        if let Some(a) = self.programs[i].asset_by_name(scope) {
+            a.insert_tag(Tag {
+                name: tag::SHARED.to_string(),
+                values: vec![Value::from(with)],
+                ..Default::default()
+            });
+        }
+    }
+
+    // Assets in more than one program and CIDRs within CIDRs of other programs
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let names = |programs: &[usize]| {
+            programs
+                .iter()
+                .map(|&i| self.programs[i].name.clone())
+                .collect()
+        };
+
+        // Equal assets under the normalization of the program they are found in,
+        // as program and asset positions
+        let mut seen: HashSet<Vec<(usize, usize)>> = HashSet::new();
+        let mut conflicts = vec![];
+        for (i, p) in self.programs.iter().enumerate() {
+            for (k, a) in p.assets.iter().enumerate() {
+                let mut owners = vec![(i, k)];
+                for &j in self.index.get(&key(&a.name)) {
+                    if j != i && !owners.iter().any(|(o, _)| *o == j) {
+                        if let Some(l) = self.programs[j].asset_position(&a.name) {
+                            owners.push((j, l));
+                        }
+                    }
+                }
+                if owners.len() < 2 {
+                    continue;
+                }
+                owners.sort();
+                if !seen.insert(owners.clone()) {
+                    continue;
+                }
+
+                let mut assets: Vec<String> = vec![];
+                for (j, l) in &owners {
+                    let name = self.programs[*j].assets[*l].name.to_string();
+                    if !assets.contains(&name) {
+                        assets.push(name);
+                    }
+                }
+                let programs: Vec<usize> = owners.iter().map(|(j, _)| *j).collect();
+                conflicts.push(Conflict {
+                    asset: assets.join(" = "),
+                    programs: names(&programs),
+                });
+            }
+        }
+
+        for (i, p) in self.programs.iter().enumerate() {
+            for a in p
+                .assets
+                .iter()
+                .filter(|a| matches!(a.name, AssetName::Cidr(_)))
+            {
+                for (j, range) in self.owners(&a.name) {
+                    if i != j && range != a.name {
+                        conflicts.push(Conflict {
+                            asset: format!("{} (in {range})", a.name),
+                            programs: names(&[i, j]),
+                        });
+                    }
+                }
+            }
+        }
+
+        conflicts.sort_by(|a, b| a.asset.cmp(&b.asset));
+        conflicts
+    }
+
+    // Insert into an indexed program
+    fn insert_into(&mut self, i: usize, asset: Asset) {
+        let from = self.programs[i].assets.len();
+        self.programs[i].insert_asset(asset);
+        self.index_program(i, from);
+    }
+
     pub fn insert_asset(&mut self, asset: Asset, program: Option<Program>) -> Result<(), Errors> {
-        if let Some(a) = self.asset_by_name(&asset.name) {
-            a.merge(asset);
-        } else if let Some(domain) = asset.name.domain() {
-            if let Some(p) = self.program_by_asset(&domain) {
-                p.insert_asset(asset);
-            } else if let Some(mut p) = program {
-                p.insert_asset(asset);
-                self.insert_program(p)?;
-            } else {
-                return Err(format!("OOP: {}", asset.name).into());
+        match self.screen(asset, program.as_ref()) {
+            Some(asset) => self.put_asset(asset, program),
+            None => Ok(()),
+        }
+    }
+
+    // Assets of a given program follow its conflict policy, others go to the
+    // first program they belong to and to the ones that allow conflicts
+    fn put_asset(&mut self, asset: Asset, program: Option<Program>) -> Result<(), Errors> {
+        if let Some(mut p) = program {
+            p.insert_asset(asset);
+            return self.insert_program(p);
+        }
+
+        let mut owners = self.owners(&asset.name);
+        owners.sort_by_key(|(i, _)| *i);
+        let Some(&(first, _)) = owners.first() else {
+            return match asset.name.domain() {
+                Some(_) => Err(format!("OOP: {}", asset.name).into()),
+                None => Err(format!("OOS: {}", asset.name).into()),
+            };
+        };
+        for &(i, _) in &owners[1..] {
+            if self.programs[i].on_conflict() == OnConflict::Allow {
+                self.insert_into(i, asset.clone());
             }
-        } else {
-            return Err(format!("OOS: {}", asset.name).into());
         }
+        self.insert_into(first, asset);
         Ok(())
     }
 
+    // Programs the name belongs to, with the asset of each that holds it: the
+    // domain, the smallest range that contains it or the name itself
+    fn owners(&self, name: &AssetName) -> Vec<(usize, AssetName)> {
+        let scopes: Vec<AssetName> = match name {
+            AssetName::Cidr(net) => (0..=net.prefix_len())
+                .rev()
+                .filter_map(|len| IpNet::new(net.network(), len).ok())
+                .map(|n| AssetName::Cidr(n.trunc()))
+                .collect(),
+            AssetName::Url(req) if name.domain().is_none() => {
+                return match req.url.host() {
+                    Some(Host::Ipv4(ip)) => self.owners(&AssetName::Cidr(IpAddr::V4(ip).into())),
+                    Some(Host::Ipv6(ip)) => self.owners(&AssetName::Cidr(IpAddr::V6(ip).into())),
+                    _ => vec![],
+                };
+            }
+            name => name.domain().into_iter().collect(),
+        };
+
+        let mut owners: Vec<(usize, AssetName)> = vec![];
+        for scope in scopes.into_iter().chain([name.clone()]) {
+            for &i in self.index.get(&key(&scope)) {
+                if !owners.iter().any(|(j, _)| *j == i)
+                    && self.programs[i].asset_position(&scope).is_some()
+                {
+                    owners.push((i, scope.clone()));
+                }
+            }
+        }
+        owners
+    }
+
+    // Generated names that aren't known yet, tagged to be verified later
+    pub fn insert_unverified(&mut self, names: &[String], source: &str) -> usize {
+        let mut inserted = 0;
+        for name in names.iter().filter_map(|n| AssetName::from_str(n).ok()) {
+            if self.contains(&name) {
+                continue;
+            }
+
+            let tag = Tag {
+                name: tag::UNVERIFIED.to_string(),
+                values: vec![Value::from(source)],
+                ..Default::default()
+            };
+            let asset = Asset {
+                name,
+                tags: vec![tag],
+                start: Time::default(),
+                update: None,
+                dns: None,
+                wildcard: None,
+            };
+            if self.insert_asset(asset, None).is_ok() {
+                inserted += 1;
+            }
+        }
+        inserted
+    }
+
+    // Names that resolve are seen and verified, unknown ones are inserted
+    pub fn insert_dns(&mut self, name: AssetName, dns: Dns) -> Result<(), Errors> {
+        let asset = Asset {
+            name,
+            tags: vec![],
+            start: dns.time,
+            update: None,
+            dns: Some(dns),
+            wildcard: None,
+        };
+        let mut asset = match self.screen(asset, None) {
+            Some(asset) => asset,
+            None => return Ok(()),
+        };
+
+        let dns = asset.dns.take().unwrap_or_default();
+        let verified = dns.exists() && !dns.wildcard;
+
+        match self.asset_by_name(&asset.name) {
+            Some(a) => {
+                if verified {
+                    let seen = a.seen().max(dns.time);
+                    a.update = (seen > a.start).then_some(seen);
+                    a.tags
+                        .retain(|t| t.name != tag::UNVERIFIED && t.name != tag::WILDCARD);
+                }
+                a.dns = Some(dns);
+                for tag in asset.tags {
+                    a.insert_tag(tag);
+                }
+                Ok(())
+            }
+            None => {
+                asset.dns = Some(dns);
+                self.put_asset(asset, None)
+            }
+        }
+    }
+
+    // Fingerprint of the domain, empty records if it has no wildcard
+    pub fn insert_wildcard(&mut self, domain: &AssetName, fingerprint: Dns) -> bool {
+        match self.asset_by_name(domain) {
+            Some(asset) => {
+                asset.wildcard = Some(fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Subdomains answered by the wildcard of their domain are tagged or dropped,
+    // new ones not resolved yet are tagged to be verified by a resolve
+    fn screen(&self, mut asset: Asset, program: Option<&Program>) -> Option<Asset> {
+        if !matches!(asset.name, AssetName::Subdomain(_)) {
+            return Some(asset);
+        }
+
+        let domain = asset.name.domain();
+        let known = domain
+            .as_ref()
+            .and_then(|d| self.program_position_by_asset(d))
+            .map(|i| &self.programs[i]);
+        let fingerprint = known.zip(domain.as_ref()).and_then(|(p, d)| {
+            p.asset_position(d)
+                .and_then(|i| p.assets[i].wildcard.as_ref())
+        });
+
+        let dns = match &mut asset.dns {
+            Some(dns) if dns.exists() => dns,
+            Some(_) => return Some(asset),
+            None => {
+                if fingerprint.is_some_and(|f| f.exists()) && !self.contains(&asset.name) {
+                    asset.insert_tag(Tag {
+                        name: tag::UNVERIFIED.to_string(),
+                        values: vec![Value::from(tag::WILDCARD)],
+                        ..Default::default()
+                    });
+                }
+                return Some(asset);
+            }
+        };
+
+        if fingerprint.is_some_and(|f| dns.within(f)) {
+            dns.wildcard = true;
+        }
+        if !dns.wildcard {
+            return Some(asset);
+        }
+
+        match known
+            .or(program)
+            .map(|p| p.on_wildcard())
+            .unwrap_or_default()
+        {
+            OnWildcard::Drop => {
+                debug!("Wildcard dropped: {}", asset.name);
+                None
+            }
+            OnWildcard::Tag => {
+                asset.insert_tag(Tag {
+                    name: tag::WILDCARD.to_string(),
+                    ..Default::default()
+                });
+                Some(asset)
+            }
+            OnWildcard::Keep => Some(asset),
+        }
+    }
+
     pub fn insert_tag(&mut self, tag: Tag, asset: &AssetName) -> Result<(), Errors> {
         if let Some(asset) = self.asset_by_name(asset) {
             asset.insert_tag(tag);
             Ok(())
         } else if let Some(domain) = asset.domain() {
-            if let Some(pr) = self.program_by_asset(&domain) {
-                let asset = Asset {
-                    name: asset.to_owned(),
-                    tags: vec![tag],
-                    start: Time::default(),
-                };
-                let idx = pr.assets.binary_search(&asset).unwrap_or_else(|x| x);
-                pr.assets.insert(idx, asset);
+            if let Some(i) = self.program_position_by_asset(&domain) {
+                self.insert_into(
+                    i,
+                    Asset {
+                        name: asset.to_owned(),
+                        tags: vec![tag],
+                        start: Time::default(),
+                        update: None,
+                        dns: None,
+                        wildcard: None,
+                    },
+                );
                 Ok(())
             } else {
                 Err("OOS".into())
@@ -94,128 +482,274 @@ impl Luna {
         }
     }
 
+    pub fn program_position(&self, name: &str) -> Option<usize> {
+        self.names.get(&name.to_lowercase()).first().copied()
+    }
     pub fn program_by_name(&mut self, name: &str) -> Option<&mut Program> {
-        self.programs
-            .iter_mut()
-            .find(|p| p.name.to_lowercase() == name.to_lowercase())
+        self.program_position(name).map(|i| &mut self.programs[i])
     }
-    pub fn program_by_asset(&mut self, asset: &AssetName) -> Option<&mut Program> {
-        self.programs.iter_mut().find(|p| {
-            if let Some(domain) = asset.domain() {
-                p.assets
-                    .binary_search(&Asset {
-                        name: domain,
-                        tags: vec![],
-                        start: time::Time::default(),
-                    })
-                    .is_ok()
-            } else {
-                p.assets
-                    .binary_search(&Asset {
-                        name: asset.to_owned(),
-                        tags: vec![],
-                        start: time::Time::default(),
-                    })
-                    .is_ok()
-            }
-        })
+
+    pub fn rename_program(&mut self, name: &str, to: &str) -> Result<(), Errors> {
+        let i = self
+            .program_position(name)
+            .ok_or_else(|| format!("No program: {name}"))?;
+        if to.trim().is_empty() {
+            return Err("Empty program name".into());
+        }
+        if self.program_position(to).is_some_and(|j| j != i) {
+            return Err(format!("{to} exists, merge them instead").into());
+        }
+
+        self.programs[i].name = to.to_string();
+        self.reindex();
+        Ok(())
     }
 
-    pub fn asset_by_name(&mut self, name: &AssetName) -> Option<&mut Asset> {
-        let a = Asset {
-            name: name.clone(),
-            tags: vec![],
-            start: time::Time::default(),
-        };
+    // Assets and metadata of the first one go to the second one
+    pub fn merge_programs(&mut self, name: &str, into: &str) -> Result<(), Errors> {
+        let i = self
+            .program_position(name)
+            .ok_or_else(|| format!("No program: {name}"))?;
+        let j = self
+            .program_position(into)
+            .ok_or_else(|| format!("No program: {into}"))?;
+        if i == j {
+            return Err("Can't merge a program into itself".into());
+        }
+
+        let program = self.programs.remove(i);
+        let j = if i < j { j - 1 } else { j };
+        self.programs[j].merge(program);
+        self.programs[j].aggregate();
+        self.reindex();
+        Ok(())
+    }
+
+    // Moves a domain with all of its subdomains and urls, or a cidr with the ranges,
+    // IPs and urls of IPs in it, returns the number of assets
+    pub fn move_asset(&mut self, name: &AssetName, to: &str) -> Result<usize, Errors> {
+        if !matches!(name, AssetName::Domain(_) | AssetName::Cidr(_)) {
+            return Err(format!("Only domains and CIDRs can be moved: {name}").into());
+        }
+        let i = self
+            .program_position_by_asset(name)
+            .ok_or_else(|| format!("No asset: {name}"))?;
+        let j = self
+            .program_position(to)
+            .ok_or_else(|| format!("No program: {to}"))?;
+        if i == j {
+            return Ok(0);
+        }
 
-        for p in &mut self.programs {
-            if let Ok(i) = p.assets_search(&a) {
-                return p.assets.get_mut(i);
+        let within = |a: &AssetName| {
+            let host = match a {
+                AssetName::Subdomain(host) => Some(host.to_owned()),
+                AssetName::Url(req) => req.url.host().map(|h| h.to_owned()),
+                _ => None,
+            };
+            match (name, a, host) {
+                (AssetName::Cidr(net), AssetName::Cidr(c), _) => net.contains(c),
+                (AssetName::Cidr(net), _, Some(Host::Ipv4(ip))) => net.contains(&IpAddr::from(ip)),
+                (AssetName::Cidr(net), _, Some(Host::Ipv6(ip))) => net.contains(&IpAddr::from(ip)),
+                _ => a.domain().as_ref() == Some(name),
             }
+        };
+        let (moved, kept) = self.programs[i]
+            .take_assets()
+            .into_iter()
+            .partition::<Vec<Asset>, _>(|a| within(&a.name));
+        self.programs[i].assets = kept;
+
+        let len = moved.len();
+        for asset in moved {
+            self.programs[j].insert_asset(asset);
         }
-        None
+        self.reindex();
+        Ok(len)
     }
 
-    pub fn programs(&self, filter: &Filter) -> Vec<&Program> {
-        self.programs
+    pub fn program_position_by_asset(&self, asset: &AssetName) -> Option<usize> {
+        let name = asset.domain().unwrap_or_else(|| asset.to_owned());
+        self.index
+            .get(&key(&name))
             .iter()
-            .filter(|p| filter.program(p))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+            .copied()
+            .find(|&i| self.programs[i].asset_position(&name).is_some())
+    }
+    pub fn program_by_asset(&mut self, asset: &AssetName) -> Option<&mut Program> {
+        self.program_position_by_asset(asset)
+            .map(|i| &mut self.programs[i])
+    }
+
+    pub fn contains(&self, name: &AssetName) -> bool {
+        self.index
+            .get(&key(name))
+            .iter()
+            .any(|&i| self.programs[i].asset_position(name).is_some())
+    }
+
+    pub fn asset_by_name(&mut self, name: &AssetName) -> Option<&mut Asset> {
+        let i = self
+            .index
+            .get(&key(name))
+            .iter()
+            .copied()
+            .find(|&i| self.programs[i].asset_position(name).is_some())?;
+        self.programs[i].asset_by_name(name)
+    }
+
+    pub fn programs(&self, filter: &Filter) -> Vec<&Program> {
+        filter.page(
+            self.programs
+                .iter()
+                .filter(|p| filter.program(p))
+                .filter(|p| filter.time(p.start, p.seen())),
+            |p| *p,
+        )
     }
     pub fn programs_mut(&mut self, filter: &Filter) -> Vec<&mut Program> {
-        self.programs
-            .iter_mut()
-            .filter(|p| filter.program(p))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+        filter.page(
+            self.programs
+                .iter_mut()
+                .filter(|p| filter.program(p))
+                .filter(|p| filter.time(p.start, p.seen())),
+            |p| &**p,
+        )
     }
-    pub fn assets(&self, field: Field, filter: &Filter) -> Vec<&Asset> {
+    // Matching assets along with their program
+    fn asset_iter<'a: 'f, 'f>(
+        &'a self,
+        field: Field,
+        filter: &'f Filter,
+    ) -> impl Iterator<Item = (&'a Program, &'a Asset)> + 'f {
         self.programs
             .iter()
             .filter(|p| filter.program(p))
-            .flat_map(|p| &p.assets)
-            .filter(|a| {
-                matches!(
-                    (&a.name, field),
-                    (AssetName::Domain(_), Field::Domain)
-                        | (AssetName::Subdomain(_), Field::Sub)
-                        | (AssetName::Url(_), Field::Url)
-                        | (AssetName::Cidr(_), Field::Cidr)
-                        | (_, Field::Asset)
-                )
+            .flat_map(|p| {
+                let q = filter.query(p);
+                p.assets
+                    .iter()
+                    .filter(move |a| q.asset(a))
+                    .map(move |a| (p, a))
             })
-            .filter(|a| filter.asset(a))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+            .filter(move |(_, a)| field.asset(&a.name))
+            .filter(|(_, a)| filter.asset(a))
+            .filter(|(_, a)| filter.time(a.start, a.seen()))
+    }
+    pub fn assets(&self, field: Field, filter: &Filter) -> Vec<&Asset> {
+        filter.page(self.asset_iter(field, filter).map(|(_, a)| a), |a| *a)
     }
     pub fn assets_mut(&mut self, field: Field, filter: &Filter) -> Vec<&mut Asset> {
+        filter.page(
+            self.programs
+                .iter_mut()
+                .filter(|p| filter.program(p))
+                .flat_map(|p| {
+                    let q = filter.query(p);
+                    p.assets.iter_mut().filter(move |a| q.asset(a))
+                })
+                .filter(|a| field.asset(&a.name))
+                .filter(|a| filter.asset(a))
+                .filter(|a| filter.time(a.start, a.seen())),
+            |a| &**a,
+        )
+    }
+    // Matching tags along with their asset and program
+    fn tag_iter<'a: 'f, 'f>(
+        &'a self,
+        filter: &'f Filter,
+    ) -> impl Iterator<Item = (&'a Program, &'a Asset, &'a Tag)> + 'f {
         self.programs
-            .iter_mut()
+            .iter()
             .filter(|p| filter.program(p))
-            .flat_map(|p| &mut p.assets)
-            .filter(|a| {
-                matches!(
-                    (&a.name, field),
-                    (AssetName::Domain(_), Field::Domain)
-                        | (AssetName::Subdomain(_), Field::Sub)
-                        | (AssetName::Url(_), Field::Url)
-                        | (AssetName::Cidr(_), Field::Cidr)
-                        | (_, Field::Asset)
-                )
+            .flat_map(|p| {
+                let q = filter.query(p);
+                p.assets
+                    .iter()
+                    .filter(|a| filter.asset(a))
+                    .flat_map(move |a| {
+                        let q = q.bind_asset(a);
+                        a.tags
+                            .iter()
+                            .filter(move |t| q.tag(t))
+                            .map(move |t| (p, a, t))
+                    })
             })
-            .filter(|a| filter.asset(a))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+            .filter(|(_, _, t)| filter.tag(t))
+            .filter(|(_, _, t)| filter.time(t.start, t.seen()))
+    }
+    // Names of matching assets of each program
+    pub fn find_by_program(&self, field: Field, filter: &Filter) -> Vec<(&Program, Vec<String>)> {
+        let mut groups: Vec<(&Program, Vec<String>)> = vec![];
+        for (p, a) in filter.page(self.asset_iter(field, filter), |(_, a)| *a) {
+            match groups.iter_mut().find(|(g, _)| std::ptr::eq(*g, p)) {
+                Some((_, names)) => names.push(a.stringify(0)),
+                None => groups.push((p, vec![a.stringify(0)])),
+            }
+        }
+        groups
     }
     pub fn tags(&self, filter: &Filter) -> Vec<&Tag> {
-        self.programs
-            .iter()
-            .filter(|p| filter.program(p))
-            .flat_map(|p| &p.assets)
-            .filter(|a| filter.asset(a))
-            .flat_map(|a| &a.tags)
-            .filter(|t| filter.tag(t))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+        filter.page(self.tag_iter(filter).map(|(_, _, t)| t), |t| *t)
+    }
+    // Matching tags along with their asset and program
+    pub fn tag_entries(&self, filter: &Filter) -> Vec<(&Program, &Asset, &Tag)> {
+        filter.page(self.tag_iter(filter), |(_, _, t)| *t)
     }
     pub fn tags_mut(&mut self, filter: &Filter) -> Vec<&mut Tag> {
-        self.programs
-            .iter_mut()
-            .filter(|p| filter.program(p))
-            .flat_map(|p| &mut p.assets)
-            .filter(|a| filter.asset(a))
-            .flat_map(|a| &mut a.tags)
-            .filter(|t| filter.tag(t))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
-            .collect()
+        filter.page(
+            self.programs
+                .iter_mut()
+                .filter(|p| filter.program(p))
+                .flat_map(|p| {
+                    let q = filter.query(p);
+                    p.assets
+                        .iter_mut()
+                        .filter(|a| filter.asset(a))
+                        .flat_map(move |a| {
+                            let q = q.bind_asset(a);
+                            a.tags.iter_mut().filter(move |t| q.tag(t))
+                        })
+                })
+                .filter(|t| filter.tag(t))
+                .filter(|t| filter.time(t.start, t.seen())),
+            |t| &**t,
+        )
+    }
+
+    pub fn table(&self, field: Field, filter: &Filter, fields: &Fields) -> Table {
+        let keys = fields.keys();
+        let level = query::Level::from(field);
+        let row = |p: &Program, a: Option<&Asset>, t: Option<&Tag>| {
+            keys.iter().map(|k| k.values(p, a, t)).collect()
+        };
+
+        let rows = match field {
+            Field::Luna | Field::None => vec![],
+            Field::Program => self
+                .programs(filter)
+                .into_iter()
+                .map(|p| row(p, None, None))
+                .collect(),
+            Field::Tag | Field::Value => self
+                .tag_entries(filter)
+                .into_iter()
+                .map(|(p, a, t)| row(p, Some(a), Some(t)))
+                .collect(),
+            _ => filter
+                .page(self.asset_iter(field, filter), |(_, a)| *a)
+                .into_iter()
+                .map(|(p, a)| row(p, Some(a), None))
+                .collect(),
+        };
+
+        Table {
+            names: fields.0.iter().map(|(n, _)| n.clone()).collect(),
+            lists: keys.iter().map(|k| k.is_list(level)).collect(),
+            rows,
+        }
     }
+
     pub fn find(&self, field: Field, filter: &Filter, v: u8) -> Vec<String> {
         match field {
             Field::Luna => vec![self.stringify(v)],
@@ -225,8 +759,14 @@ impl Luna {
                 .map(|p| p.stringify(v))
                 .collect(),
             Field::None => vec!["".to_string()],
-            Field::Tag => self.tags(filter).iter().map(|t| t.stringify(v)).collect(),
-            Field::Value => self.tags(filter).iter().map(|t| t.stringify(v)).collect(),
+            Field::Tag | Field::Value if v == 0 => {
+                self.tags(filter).iter().map(|t| t.stringify(v)).collect()
+            }
+            Field::Tag | Field::Value => self
+                .tag_entries(filter)
+                .into_iter()
+                .map(|(p, a, t)| path(p, a, t, v))
+                .collect(),
             Field::Cidr if v == 2 => self
                 .assets(field, filter)
                 .iter()
@@ -252,30 +792,134 @@ impl Luna {
         }
     }
 
+    // Tag paths grouped by program or by severity, the most severe first
+    pub fn group(&self, by: Group, filter: &Filter, v: u8) -> Vec<(String, Vec<String>)> {
+        let mut groups: Vec<(String, Vec<String>)> = vec![];
+        for (p, a, t) in self.tag_entries(filter) {
+            let key = match by {
+                Group::Program => p.name.clone(),
+                Group::Severity => t
+                    .severity
+                    .as_ref()
+                    .map_or("none", |s| s.as_str())
+                    .to_lowercase(),
+            };
+            let path = path(p, a, t, v);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, paths)) => paths.push(path),
+                None => groups.push((key, vec![path])),
+            }
+        }
+
+        if let Group::Severity = by {
+            groups.sort_by_key(|(k, _)| std::cmp::Reverse(severity::key_order(k)));
+        }
+        groups
+    }
+
+    // Matching values of the filtered tags counted together, the most found first
+    pub fn values(&self, filter: &Filter) -> Vec<Value> {
+        let mut values: Vec<Value> = vec![];
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for tag in self.tags(filter) {
+            for value in tag.values.iter().filter(|v| filter.value(&v.value)) {
+                match index.get(value.value.as_str()) {
+                    Some(i) => values[*i].merge(value.clone()),
+                    None => {
+                        index.insert(&value.value, values.len());
+                        values.push(value.clone());
+                    }
+                }
+            }
+        }
+        values.sort_by_key(|v| std::cmp::Reverse(v.count));
+        values
+    }
+
+    // Triage of the filtered tags
+    pub fn triage(&mut self, filter: &Filter, triage: &Triage) -> usize {
+        let mut tags = self.tags_mut(filter);
+        for tag in tags.iter_mut() {
+            tag.triage(triage);
+        }
+        tags.len()
+    }
+
     pub fn remove(&mut self, field: Field, filter: &Filter) {
         match field {
             Field::Luna => error!("WTF!"),
             Field::Program => self.programs.retain(|p| !filter.program(p)),
             Field::None => debug!("!"),
-            Field::Tag => self
-                .assets_mut(Field::Asset, filter)
-                .iter_mut()
-                .for_each(|a| a.tags.retain(|t| !filter.tag(t))),
+            Field::Tag => self.programs_mut(filter).into_iter().for_each(|p| {
+                let q = filter.query(p);
+                p.assets
+                    .iter_mut()
+                    .filter(|a| filter.asset(a))
+                    .filter(|a| filter.time(a.start, a.seen()))
+                    .for_each(|a| {
+                        let q = q.bind_asset(a);
+                        a.tags.retain(|t| !(filter.tag(t) && q.tag(t)))
+                    })
+            }),
             Field::Value => self
                 .tags_mut(filter)
                 .iter_mut()
-                .for_each(|t| t.values.retain(|v| !filter.value(v))),
+                .for_each(|t| t.values.retain(|v| !filter.value(&v.value))),
             _ => self.programs_mut(filter).iter_mut().for_each(|p| {
+                let q = filter.query(p);
+                let matched = |a: &Asset| filter.asset(a) && q.asset(a);
                 p.assets.retain(|a| match (field, &a.name) {
-                    (Field::Domain, AssetName::Domain(_)) => !filter.asset(a),
-                    (Field::Sub, AssetName::Subdomain(_)) => !filter.asset(a),
-                    (Field::Url, AssetName::Url(_)) => !filter.asset(a),
-                    (Field::Cidr, AssetName::Cidr(_)) => !filter.asset(a),
-                    (Field::Asset, _) => !filter.asset(a),
+                    (Field::Domain, AssetName::Domain(_)) => !matched(a),
+                    (Field::Sub, AssetName::Subdomain(_)) => !matched(a),
+                    (Field::Url, AssetName::Url(_)) => !matched(a),
+                    (Field::Cidr, AssetName::Cidr(_)) => !matched(a),
+                    (Field::Asset, _) => !matched(a),
                     _ => true,
                 })
             }),
         }
+
+        self.reindex();
+    }
+
+    // Replace the view with the same name
+    pub fn save_view(&mut self, view: View) -> Result<(), Errors> {
+        view.filter()?;
+        match self.views.iter_mut().find(|v| v.name == view.name) {
+            Some(v) => *v = view,
+            None => self.views.push(view),
+        }
+        Ok(())
+    }
+
+    pub fn remove_view(&mut self, name: &str) -> bool {
+        let len = self.views.len();
+        self.views.retain(|v| v.name != name);
+        len != self.views.len()
+    }
+
+    // Merge the view named in the filter into it
+    pub fn resolve(&self, filter: &mut Filter) -> Result<(), Errors> {
+        if let Some(name) = filter.view.take() {
+            let view = self
+                .views
+                .iter()
+                .find(|v| v.name == name)
+                .ok_or_else(|| format!("No such view: {name}"))?;
+            filter.merge(view.filter()?);
+        }
+        Ok(())
+    }
+
+    // Set the url normalization policy of programs and merge their urls again
+    pub fn normalize(&mut self, policy: Option<Normalization>, filter: &Filter) -> usize {
+        self.programs_mut(filter)
+            .into_iter()
+            .map(|p| {
+                merge(&mut p.normalization, policy, true);
+                p.normalize()
+            })
+            .sum()
     }
 
     pub fn save_as(&self, path: &Path, backup: bool) -> Result<usize, Errors> {
@@ -303,12 +947,12 @@ impl Luna {
                 )
             };
 
-            std::fs::copy(&path, to)?;
+            std::fs::copy(path, to)?;
         }
         match std::fs::File::options()
             .write(true)
             .truncate(true)
-            .open(&path)
+            .open(path)
         {
             Ok(mut file) => Ok(file.write(str.as_bytes())?),
             Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
@@ -330,7 +974,9 @@ impl Luna {
 
     pub fn from_file(path: &Path) -> Result<Self, Errors> {
         let file = std::fs::read_to_string(path)?;
-        Ok(serde_json::from_str(&file)?)
+        let mut luna: Self = serde_json::from_str(&file)?;
+        luna.reindex();
+        Ok(luna)
     }
 
     pub fn parse(path: &Path) -> Luna {
@@ -354,8 +1000,10 @@ impl Luna {
         match v {
             0 => self.name.to_string(),
             1 => format!("{} {}", self.name, self.version),
-            2 => format!(
-                "{}  {}
+            2 => {
+                let c = self.count();
+                format!(
+                    "{}  {}
     Status:   {}
     Programs: {}
     Assets:   {}
@@ -366,23 +1014,26 @@ impl Luna {
     Tags:     {}
     Start:    {}
     ",
-                self.name,
-                self.version,
-                self.status,
-                self.programs.len(),
-                self.find(Field::Asset, &Filter::default(), 0).len(),
-                self.find(Field::Domain, &Filter::default(), 0).len(),
-                self.find(Field::Cidr, &Filter::default(), 0).len(),
-                self.find(Field::Sub, &Filter::default(), 0).len(),
-                self.find(Field::Url, &Filter::default(), 0).len(),
-                self.find(Field::Tag, &Filter::default(), 0).len(),
-                self.start
-                    .0
-                    .with_timezone(&Local::now().timezone())
-                    .to_rfc2822(),
-            ),
-            3 => format!(
-                "{}  {}
+                    self.name,
+                    self.version,
+                    self.status,
+                    self.programs.len(),
+                    c.assets,
+                    c.domains,
+                    c.cidrs,
+                    c.subs,
+                    c.urls,
+                    c.tags,
+                    self.start
+                        .0
+                        .with_timezone(&Local::now().timezone())
+                        .to_rfc2822(),
+                )
+            }
+            3 => {
+                let c = self.count();
+                format!(
+                    "{}  {}
     Status:   {}
     Programs: [{}{}
     Assets:   {}
@@ -393,31 +1044,32 @@ impl Luna {
     Tags:     {}
     Start:    {}
     ",
-                self.name,
-                self.version,
-                self.status,
-                self.programs
-                    .iter()
-                    .filter(|p| !p.name.is_empty())
-                    .map(|p| format!("\n        {}", p.stringify(1)))
-                    .collect::<Vec<String>>()
-                    .join(""),
-                if self.programs.iter().filter(|p| !p.name.is_empty()).count() == 0 {
-                    "]"
-                } else {
-                    "\n    ]"
-                },
-                self.find(Field::Asset, &Filter::default(), 0).len(),
-                self.find(Field::Domain, &Filter::default(), 0).len(),
-                self.find(Field::Cidr, &Filter::default(), 0).len(),
-                self.find(Field::Sub, &Filter::default(), 0).len(),
-                self.find(Field::Url, &Filter::default(), 0).len(),
-                self.find(Field::Tag, &Filter::default(), 0).len(),
-                self.start
-                    .0
-                    .with_timezone(&Local::now().timezone())
-                    .to_rfc2822(),
-            ),
+                    self.name,
+                    self.version,
+                    self.status,
+                    self.programs
+                        .iter()
+                        .filter(|p| !p.name.is_empty())
+                        .map(|p| format!("\n        {}", p.stringify(1)))
+                        .collect::<Vec<String>>()
+                        .join(""),
+                    if self.programs.iter().filter(|p| !p.name.is_empty()).count() == 0 {
+                        "]"
+                    } else {
+                        "\n    ]"
+                    },
+                    c.assets,
+                    c.domains,
+                    c.cidrs,
+                    c.subs,
+                    c.urls,
+                    c.tags,
+                    self.start
+                        .0
+                        .with_timezone(&Local::now().timezone())
+                        .to_rfc2822(),
+                )
+            }
             _ => format!("{:#?}", self),
         }
     }
@@ -429,12 +1081,12 @@ mod test {
     // https://github.com/rayon-rs/rayon/issues/592#issue-357025113
     #[test]
     fn insert() {
+        use std::sync::Mutex;
+
         let mut luna = Luna::default();
         let mut program = Program::from_str("test").unwrap();
-        let asset = Asset::from_str("test.com").unwrap();
-        let idx = program.assets.binary_search(&asset).unwrap_or_else(|x| x);
-        program.assets.insert(idx, asset);
-        luna.programs.push(program);
+        program.insert_asset(Asset::from_str("test.com").unwrap());
+        luna.insert_program(program).unwrap();
 
         let i = 0;
         let i = Mutex::new(i);
@@ -448,9 +1100,7 @@ mod test {
 
                 println!("{} s", i);
 
-                if let Err(err) = luna.lock().unwrap().insert_asset(asset, None) {
-                    assert_eq!(err.to_string(), "OOS: a.test.com".to_string())
-                };
+                luna.lock().unwrap().insert_asset(asset, None).unwrap();
 
                 println!("{i} e");
                 *i += 1;
@@ -468,12 +1118,11 @@ mod test {
 
         let mut tag = Tag::from_str("sql").unwrap();
 
-        tag.severity = Some("high".to_string());
+        tag.severity = Some(Severity::High);
 
         asset.tags.push(tag);
 
-        let idx = program.assets.binary_search(&asset).unwrap_or_else(|x| x);
-        program.assets.insert(idx, asset);
+        program.insert_asset(asset);
 
         luna.insert_program(program).unwrap();
 
@@ -497,4 +1146,701 @@ mod test {
         let res = luna.find(Field::Asset, &filter, 0);
         assert!(res.is_empty());
     }
+
+    #[test]
+    fn index() {
+        let mut luna = get_luna();
+        luna.insert_program(Program::from_str("Yahoo").unwrap())
+            .unwrap();
+        luna.insert_asset(
+            Asset::from_str("yahoo.com").unwrap(),
+            Some(Program::from_str("yahoo").unwrap()),
+        )
+        .unwrap();
+        luna.insert_asset(Asset::from_str("http://mail.yahoo.com/a/1").unwrap(), None)
+            .unwrap();
+
+        assert_eq!(luna.programs.len(), 2);
+        assert_eq!(luna.program_position("YAHOO"), Some(1));
+        assert_eq!(
+            luna.program_position_by_asset(&AssetName::from_str("api.google.com").unwrap()),
+            Some(0)
+        );
+        assert!(luna
+            .asset_by_name(&AssetName::from_str("http://mail.yahoo.com/a/2").unwrap())
+            .is_some());
+        assert!(luna
+            .asset_by_name(&AssetName::from_str("mail.yahoo.com").unwrap())
+            .is_some());
+
+        let filter = Filter {
+            program: Some(filter::Regex::from_str("google").unwrap()),
+            ..Default::default()
+        };
+        luna.remove(Field::Program, &filter);
+
+        assert_eq!(luna.program_position("yahoo"), Some(0));
+        assert!(luna
+            .program_by_asset(&AssetName::from_str("google.com").unwrap())
+            .is_none());
+        assert!(luna
+            .insert_asset(Asset::from_str("a.google.com").unwrap(), None)
+            .is_err());
+    }
+
+    #[test]
+    fn query() {
+        let mut luna = get_luna();
+        luna.insert_asset(Asset::from_str("cdn.google.com").unwrap(), None)
+            .unwrap();
+        luna.insert_tag(
+            Tag::from_str("waf").unwrap(),
+            &AssetName::from_str("cdn.google.com").unwrap(),
+        )
+        .unwrap();
+        luna.insert_asset(Asset::from_str("api.google.com").unwrap(), None)
+            .unwrap();
+
+        let filter = |s: &str| Filter {
+            query: Some(Query::from_str(s).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            luna.find(Field::Sub, &filter("not tag = waf"), 0),
+            vec!["api.google.com"]
+        );
+        assert_eq!(
+            luna.find(Field::Tag, &filter("tag = waf or sv = high"), 0),
+            vec!["sql", "waf"]
+        );
+        assert_eq!(
+            luna.find(Field::Tag, &filter("asset ~ cdn and tag ~ .*"), 0),
+            vec!["waf"]
+        );
+        assert_eq!(
+            luna.find(Field::Tag, &filter("sv >= medium"), 0),
+            vec!["sql"]
+        );
+        assert!(luna.find(Field::Tag, &filter("sv > high"), 0).is_empty());
+
+        let min = |sv: &str| Filter {
+            min_severity: Some(Severity::from_str(sv).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(luna.find(Field::Tag, &min("Med"), 0), vec!["sql"]);
+        assert!(luna.find(Field::Tag, &min("critical"), 0).is_empty());
+        assert_eq!(luna.find(Field::Sub, &min("info"), 0), Vec::<String>::new());
+
+        luna.remove(Field::Sub, &filter("program = google and not asset ~ api"));
+        assert_eq!(
+            luna.find(Field::Sub, &Filter::default(), 0),
+            vec!["api.google.com"]
+        );
+    }
+
+    #[test]
+    fn sort() {
+        let mut luna = get_luna();
+        for (url, sc) in [
+            ("https://b.google.com/", "404"),
+            ("https://a.google.com/", "200"),
+            ("https://c.google.com/", "302"),
+        ] {
+            let mut asset = Asset::from_str(url).unwrap();
+            if let AssetName::Url(req) = &mut asset.name {
+                req.sc = Some(sc.to_string());
+                req.title = Some(format!("title {sc}"));
+            }
+            luna.insert_asset(asset, None).unwrap();
+        }
+
+        let filter = Filter {
+            sort: Some(Sort::Sc),
+            ..Default::default()
+        };
+        assert_eq!(
+            luna.find(Field::Url, &filter, 0),
+            vec![
+                "https://a.google.com/",
+                "https://c.google.com/",
+                "https://b.google.com/"
+            ]
+        );
+
+        let filter = Filter {
+            sort: Some(Sort::Name),
+            desc: true,
+            offset: Some(1),
+            n: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            luna.find(Field::Url, &filter, 0),
+            vec!["https://b.google.com/"]
+        );
+
+        let fields = Fields::from_str("url,sc,title,program").unwrap();
+        let filter = Filter {
+            offset: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            Format::Text.render(&luna.table(Field::Url, &filter, &fields)),
+            vec!["https://c.google.com/\t302\ttitle 302\tgoogle"]
+        );
+        assert_eq!(
+            Format::Jsonl.render(&luna.table(
+                Field::Tag,
+                &Filter::default(),
+                &Fields::of(Field::Tag)
+            )),
+            vec![
+                r#"{"program":"google","asset":"google.com","tag":"sql","severity":"high","value":[]}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let luna = get_luna();
+        let stats = luna.stats(&StatOptions::default());
+        let total = &stats.total;
+        assert_eq!((total.programs, total.assets, total.tags), (1, 1, 1));
+
+        let csv = stats.render(Format::Csv);
+        assert!(csv[0].starts_with("name,version,status,programs,assets"));
+        assert!(csv[1].starts_with("Luna,"));
+        assert!(stats.render(Format::Json)[0].contains(r#""domains":1"#));
+    }
+
+    #[test]
+    fn group() {
+        let mut luna = get_luna();
+        luna.insert_asset(
+            Asset::from_str("yahoo.com").unwrap(),
+            Some(Program::from_str("yahoo").unwrap()),
+        )
+        .unwrap();
+        for (name, asset, severity) in [
+            ("xss", "yahoo.com", Some("medium")),
+            ("waf", "yahoo.com", None),
+            ("rce", "google.com", Some("critical")),
+        ] {
+            let mut tag = Tag::from_str(name).unwrap();
+            tag.severity = severity.and_then(|s| Severity::from_str(s).ok());
+            luna.insert_tag(tag, &AssetName::from_str(asset).unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(
+            luna.find(Field::Tag, &Filter::default(), 1),
+            vec![
+                "google > google.com > sql [high]",
+                "google > google.com > rce [critical]",
+                "yahoo > yahoo.com > xss [medium]",
+                "yahoo > yahoo.com > waf []",
+            ]
+        );
+        assert_eq!(
+            luna.find(Field::Tag, &Filter::default(), 0),
+            vec!["sql", "rce", "xss", "waf"]
+        );
+
+        let groups = luna.group(Group::Severity, &Filter::default(), 0);
+        assert_eq!(
+            groups.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            vec!["critical", "high", "medium", "none"]
+        );
+        assert_eq!(groups[0].1, vec!["google > google.com > rce"]);
+
+        let groups = luna.group(Group::Program, &Filter::default(), 0);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].1.len(), 2);
+    }
+
+    #[test]
+    fn time() {
+        let mut luna = get_luna();
+        let mut old = Asset::from_str("old.google.com").unwrap();
+        old.start = Time::from_str("10d").unwrap();
+        luna.insert_asset(old, None).unwrap();
+        let mut stale = Asset::from_str("stale.google.com").unwrap();
+        stale.start = Time::from_str("20d").unwrap();
+        luna.insert_asset(stale, None).unwrap();
+        // Seen again
+        luna.insert_asset(Asset::from_str("old.google.com").unwrap(), None)
+            .unwrap();
+
+        let filter = |since: &str, until: Option<&str>, seen: Option<&str>| Filter {
+            start: Some(Time::from_str(since).unwrap()),
+            until: until.map(|t| Time::from_str(t).unwrap()),
+            seen_since: seen.map(|t| Time::from_str(t).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            luna.find(Field::Sub, &filter("15d", None, None), 0),
+            vec!["old.google.com"]
+        );
+        assert_eq!(
+            luna.find(Field::Sub, &filter("30d", Some("5d"), None), 0),
+            vec!["old.google.com", "stale.google.com"]
+        );
+        assert_eq!(
+            luna.find(Field::Sub, &filter("30d", None, Some("1h")), 0),
+            vec!["old.google.com"]
+        );
+    }
+
+    #[allow(dead_code)]
+    fn filter_from(args: &str) -> Filter {
+        Filter::try_parse_from(std::iter::once("luna").chain(args.split(' '))).unwrap()
+    }
+
+    #[test]
+    fn view() {
+        let mut luna = get_luna();
+        luna.insert_asset(Asset::from_str("api.google.com").unwrap(), None)
+            .unwrap();
+        luna.insert_asset(Asset::from_str("dev.google.com").unwrap(), None)
+            .unwrap();
+
+        let view = |name: &str, args: &[&str]| View {
+            name: name.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        };
+        luna.save_view(view(
+            "apis",
+            &["--asset", "api", "--query", "program = google"],
+        ))
+        .unwrap();
+        assert!(luna.save_view(view("bad", &["--sort", "foo"])).is_err());
+        assert!(luna.save_view(view("nested", &["--view", "apis"])).is_err());
+
+        let mut filter = filter_from("--view apis");
+        luna.resolve(&mut filter).unwrap();
+        assert_eq!(luna.find(Field::Sub, &filter, 0), vec!["api.google.com"]);
+
+        // Given flags take precedence
+        let mut filter = filter_from("--view apis --asset dev");
+        luna.resolve(&mut filter).unwrap();
+        assert_eq!(luna.find(Field::Sub, &filter, 0), vec!["dev.google.com"]);
+
+        // Even if they are less strict than the view
+        luna.save_view(view(
+            "strict",
+            &[
+                "-n",
+                "1",
+                "--offset",
+                "1",
+                "--since",
+                "2100-01-01",
+                "--min-sv",
+                "high",
+            ],
+        ))
+        .unwrap();
+        let mut filter = filter_from("--view strict -n 5 --offset 0 --since 2000-01-01");
+        luna.resolve(&mut filter).unwrap();
+        assert_eq!(filter.n, Some(5));
+        assert_eq!(filter.offset, Some(0));
+        assert_eq!(filter.start, Some(Time::from_str("2000-01-01").unwrap()));
+        assert_eq!(filter.min_severity, Some(Severity::High));
+
+        let mut filter = filter_from("--view nothing");
+        assert!(luna.resolve(&mut filter).is_err());
+
+        assert!(luna.remove_view("apis"));
+        assert!(!luna.remove_view("apis"));
+    }
+
+    #[test]
+    fn unverified() {
+        let mut luna = get_luna();
+        luna.insert_asset(
+            Asset::from_str("yahoo.com").unwrap(),
+            Some(Program::from_str("yahoo").unwrap()),
+        )
+        .unwrap();
+        luna.insert_asset(Asset::from_str("www.yahoo.com").unwrap(), None)
+            .unwrap();
+
+        let groups = luna.find_by_program(Field::Domain, &Filter::default());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].0.name, "yahoo");
+        assert_eq!(groups[1].1, vec!["yahoo.com"]);
+
+        let names = ["dev.yahoo.com", "www.yahoo.com", "dev.tesla.com", "-"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(luna.insert_unverified(&names, "dnsgen"), 1);
+
+        let filter = Filter {
+            tag: Some(filter::Regex::from_str("exact:unverified").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(luna.find(Field::Sub, &filter, 0), vec!["dev.yahoo.com"]);
+    }
+
+    #[test]
+    fn dns() {
+        let mut luna = get_luna();
+        luna.insert_unverified(&["new.google.com".to_string()], "dnsgen");
+        let name = AssetName::from_str("new.google.com").unwrap();
+
+        let wildcard = Dns {
+            a: vec![[1, 1, 1, 1].into()],
+            wildcard: true,
+            ..Default::default()
+        };
+        luna.insert_dns(name.clone(), wildcard).unwrap();
+        let asset = luna.asset_by_name(&name).unwrap();
+        assert!(asset.dns.as_ref().unwrap().wildcard);
+        assert_eq!(asset.tags[0].name, tag::UNVERIFIED);
+
+        let dns = Dns {
+            cname: vec!["google.com".to_string()],
+            ..Default::default()
+        };
+        luna.insert_dns(name.clone(), dns.clone()).unwrap();
+        let asset = luna.asset_by_name(&name).unwrap();
+        assert_eq!(asset.dns, Some(dns.clone()));
+        assert!(asset.tags.is_empty());
+
+        let other = AssetName::from_str("other.google.com").unwrap();
+        luna.insert_dns(other.clone(), dns).unwrap();
+        assert!(luna.contains(&other));
+    }
+
+    #[test]
+    fn wildcard() {
+        let mut luna = get_luna();
+        let domain = AssetName::from_str("google.com").unwrap();
+        let fingerprint = Dns {
+            a: vec![[9, 9, 9, 9].into()],
+            ..Default::default()
+        };
+        assert!(luna.insert_wildcard(&domain, fingerprint.clone()));
+
+        let x = AssetName::from_str("x.google.com").unwrap();
+        luna.insert_dns(x.clone(), fingerprint.clone()).unwrap();
+        let asset = luna.asset_by_name(&x).unwrap();
+        assert!(asset.dns.as_ref().unwrap().wildcard);
+        assert_eq!(asset.tags[0].name, tag::WILDCARD);
+
+        let y = AssetName::from_str("y.google.com").unwrap();
+        let dns = Dns {
+            a: vec![[1, 2, 3, 4].into()],
+            ..Default::default()
+        };
+        luna.insert_dns(y.clone(), dns).unwrap();
+        assert!(luna.asset_by_name(&y).unwrap().tags.is_empty());
+
+        luna.programs[0].wildcard = Some(OnWildcard::Drop);
+        let mut z = Asset::from_str("z.google.com").unwrap();
+        z.dns = Some(fingerprint);
+        luna.insert_asset(z, None).unwrap();
+        assert!(!luna.contains(&AssetName::from_str("z.google.com").unwrap()));
+
+        // Not resolved yet
+        let w = AssetName::from_str("w.google.com").unwrap();
+        luna.insert_asset(Asset::from_str("w.google.com").unwrap(), None)
+            .unwrap();
+        assert_eq!(
+            luna.asset_by_name(&w).unwrap().tags[0].name,
+            tag::UNVERIFIED
+        );
+    }
+
+    #[test]
+    fn triage() {
+        let mut luna = get_luna();
+        let triage = Triage {
+            status: Some(Status::FalsePositive),
+            assignee: Some("sa".to_string()),
+            note: Some("cdn error page".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(luna.triage(&filter_from("--tag sql"), &triage), 1);
+
+        // Found again by a scanner
+        let asset = AssetName::from_str("google.com").unwrap();
+        luna.insert_tag(Tag::from_str("sql").unwrap(), &asset)
+            .unwrap();
+
+        let tag = &luna.tags(&Filter::default())[0];
+        assert_eq!(tag.status(), Status::FalsePositive);
+        assert_eq!(tag.assignee.as_deref(), Some("sa"));
+        assert_eq!(tag.notes, vec!["cdn error page"]);
+
+        assert_eq!(
+            luna.find(Field::Tag, &filter_from("--status new,false-positive"), 0),
+            vec!["sql"]
+        );
+        assert!(luna
+            .find(Field::Tag, &filter_from("--status new"), 0)
+            .is_empty());
+        assert_eq!(
+            luna.find(Field::Tag, &filter_from("--query status=false-positive"), 0),
+            vec!["sql"]
+        );
+
+        let unassign = Triage {
+            assignee: Some("".to_string()),
+            ..Default::default()
+        };
+        luna.triage(&filter_from("--assignee sa"), &unassign);
+        assert!(luna.tags(&Filter::default())[0].assignee.is_none());
+    }
+
+    #[test]
+    fn values() {
+        let mut luna = get_luna();
+        let asset = AssetName::from_str("google.com").unwrap();
+        let found = |values: &[&str]| {
+            let mut tag = Tag::from_str("sql").unwrap();
+            tag.values = values.iter().map(|v| Value::from(*v)).collect();
+            tag
+        };
+
+        luna.insert_tag(found(&["id", "q"]), &asset).unwrap();
+        luna.insert_tag(found(&["id"]), &asset).unwrap();
+        let counts = |luna: &Luna| -> Vec<(String, usize)> {
+            luna.values(&Filter::default())
+                .into_iter()
+                .map(|v| (v.value, v.count))
+                .collect()
+        };
+        assert_eq!(counts(&luna), vec![("id".into(), 2), ("q".into(), 1)]);
+
+        // Values past the cap are only counted
+        let mut capped = found(&["page", "q"]);
+        capped.cap = Some(2);
+        luna.insert_tag(capped, &asset).unwrap();
+        let tag = &luna.tags(&Filter::default())[0];
+        assert_eq!(tag.values.len(), 2);
+        assert_eq!(tag.dropped, 1);
+        assert!(tag.values.iter().all(|v| v.start.is_some()));
+        assert_eq!(counts(&luna), vec![("id".into(), 2), ("q".into(), 2)]);
+
+        // Lowering the cap keeps the most found ones
+        let mut capped = found(&["id"]);
+        capped.cap = Some(1);
+        luna.insert_tag(capped, &asset).unwrap();
+        assert_eq!(counts(&luna), vec![("id".into(), 3)]);
+        assert_eq!(luna.tags(&Filter::default())[0].dropped, 3);
+
+        // Plain values of older files
+        let tag: Tag = serde_json::from_str(
+            r#"{"name":"x","values":["a","b"],"start":"2022-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(tag.values[1], Value::from("b"));
+        assert_eq!(serde_json::to_string(&tag.values).unwrap(), r#"["a","b"]"#);
+
+        // Repeated values are merged once, then found by the index
+        let mut tag: Tag = serde_json::from_str(
+            r#"{"name":"x","values":["a","b","a"],"start":"2022-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        tag.insert_value(Value::from("c"));
+        assert_eq!(tag.values.len(), 3);
+        assert_eq!(tag.values[0].count, 2);
+        assert_eq!(tag.index.len(), tag.values.len());
+        tag.insert_value(Value::from("a"));
+        assert_eq!(tag.values[0].count, 3);
+    }
+
+    #[test]
+    fn programs() {
+        let mut luna = get_luna();
+        let mut vrp = Program::from_str("google-vrp").unwrap();
+        vrp.insert_asset(Asset::from_str("https://api.youtube.com/v1").unwrap());
+        vrp.insert_asset(Asset::from_str("10.0.0.0/24").unwrap());
+        luna.insert_program(vrp).unwrap();
+        luna.insert_program(Program::from_str("alphabet").unwrap())
+            .unwrap();
+
+        assert!(luna.rename_program("Google", "google-vrp").is_err());
+        luna.rename_program("Google", "Google").unwrap();
+        assert!(luna.program_by_name("google").is_some());
+
+        let name = |s: &str| AssetName::from_str(s).unwrap();
+        assert!(luna
+            .move_asset(&name("api.youtube.com"), "alphabet")
+            .is_err());
+        assert_eq!(
+            luna.move_asset(&name("youtube.com"), "alphabet").unwrap(),
+            3
+        );
+        let alphabet = luna.program_by_name("alphabet").unwrap();
+        assert_eq!(alphabet.assets.len(), 3);
+        assert_eq!(
+            luna.program_by_asset(&name("https://api.youtube.com/v1"))
+                .unwrap()
+                .name,
+            "alphabet"
+        );
+
+        // Ranges, IPs and urls of IPs in the cidr go with it
+        let vrp = luna.program_by_name("google-vrp").unwrap();
+        vrp.insert_asset(Asset::from_str("10.0.0.0/28").unwrap());
+        vrp.insert_asset(Asset::from_str("http://10.0.0.5:8080/admin").unwrap());
+        vrp.insert_asset(Asset::from_str("http://10.0.1.5/").unwrap());
+        luna.reindex();
+        assert_eq!(
+            luna.move_asset(&name("10.0.0.0/24"), "alphabet").unwrap(),
+            4
+        );
+        assert_eq!(
+            luna.program_by_asset(&name("http://10.0.0.5:8080/admin"))
+                .unwrap()
+                .name,
+            "alphabet"
+        );
+        let vrp = luna.program_by_name("google-vrp").unwrap();
+        let kept: Vec<String> = vrp.assets.iter().map(|a| a.name.to_string()).collect();
+        assert_eq!(kept, vec!["10.0.1.5/32", "http://10.0.1.5/"]);
+        luna.move_asset(&name("10.0.0.0/24"), "google-vrp").unwrap();
+
+        luna.merge_programs("google-vrp", "google").unwrap();
+        assert!(luna.program_by_name("google-vrp").is_none());
+        assert_eq!(luna.programs.len(), 2);
+        assert_eq!(
+            luna.program_by_asset(&name("10.0.0.0/24")).unwrap().name,
+            "Google"
+        );
+        assert!(luna.merge_programs("google", "Google").is_err());
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut luna = get_luna();
+        let program = |name: &str, assets: &[&str], policy: Option<OnConflict>| {
+            let mut p = Program::from_str(name).unwrap();
+            p.assets = assets.iter().map(|a| Asset::from_str(a).unwrap()).collect();
+            p.conflict = policy;
+            p
+        };
+
+        luna.insert_program(program("vrp", &["api.google.com", "youtube.com"], None))
+            .unwrap();
+        let name = |s: &str| AssetName::from_str(s).unwrap();
+        assert_eq!(
+            luna.program_by_asset(&name("api.google.com")).unwrap().name,
+            "google"
+        );
+        assert_eq!(
+            luna.program_by_asset(&name("youtube.com")).unwrap().name,
+            "vrp"
+        );
+        let shared = luna
+            .asset_by_name(&name("google.com"))
+            .unwrap()
+            .tag_by_name(tag::SHARED)
+            .unwrap();
+        assert_eq!(shared.values, vec![Value::from("vrp")]);
+
+        let skip = Some(OnConflict::Skip);
+        luna.insert_program(program("other", &["a.youtube.com", "x.com"], skip))
+            .unwrap();
+        assert!(!luna.contains(&name("a.youtube.com")));
+        assert!(luna.conflicts().is_empty());
+
+        luna.insert_program(program("dup", &["google.com"], Some(OnConflict::Allow)))
+            .unwrap();
+        luna.insert_program(program("net", &["10.0.0.0/8"], None))
+            .unwrap();
+        luna.insert_program(program("lab", &["10.1.0.0/16"], Some(OnConflict::Allow)))
+            .unwrap();
+        let conflicts: Vec<String> = luna.conflicts().iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                "10.1.0.0/16 (in 10.0.0.0/8): lab, net",
+                "google.com: google, dup"
+            ]
+        );
+
+        // Ranges and IPs within ranges of other programs
+        luna.insert_program(program("ops", &["10.2.0.0/16", "http://10.3.0.1/a"], None))
+            .unwrap();
+        luna.insert_program(program("tmp", &["10.4.0.0/24"], skip))
+            .unwrap();
+        let net = luna.program_by_name("net").unwrap();
+        for name in ["10.2.0.0/16", "10.3.0.1/32", "http://10.3.0.1/a"] {
+            assert!(net
+                .asset_position(&AssetName::from_str(name).unwrap())
+                .is_some());
+        }
+        let shared = luna
+            .asset_by_name(&name("10.0.0.0/8"))
+            .unwrap()
+            .tag_by_name(tag::SHARED)
+            .unwrap();
+        assert_eq!(shared.values.len(), 1);
+        assert_eq!(shared.values[0].value, "ops");
+        assert!(luna.program_by_name("ops").unwrap().assets.is_empty());
+        assert!(!luna.contains(&name("10.4.0.0/24")));
+
+        // Assets without a program go to the first owner and the ones that allow conflicts
+        luna.insert_asset(Asset::from_str("10.1.2.0/24").unwrap(), None)
+            .unwrap();
+        assert!(luna
+            .program_by_name("lab")
+            .unwrap()
+            .asset_position(&name("10.1.2.0/24"))
+            .is_some());
+        assert!(luna
+            .program_by_name("net")
+            .unwrap()
+            .asset_position(&name("10.1.2.0/24"))
+            .is_some());
+        let vrp = Program::from_str("vrp").unwrap();
+        luna.insert_asset(Asset::from_str("mail.google.com").unwrap(), Some(vrp))
+            .unwrap();
+        assert!(luna
+            .program_by_name("vrp")
+            .unwrap()
+            .asset_position(&name("mail.google.com"))
+            .is_none());
+        assert!(luna
+            .program_by_name("google")
+            .unwrap()
+            .asset_position(&name("mail.google.com"))
+            .is_some());
+        assert!(luna
+            .insert_asset(Asset::from_str("192.168.0.1").unwrap(), None)
+            .is_err());
+
+        // Urls of an origin are only conflicts if they are equal
+        let allow = Some(OnConflict::Allow);
+        let mut luna = get_luna();
+        luna.insert_program(program("a", &["https://x.com/tag/1/feed"], allow))
+            .unwrap();
+        luna.insert_program(program("b", &["https://x.com/tag/2/feed"], allow))
+            .unwrap();
+        luna.insert_program(program("c", &["https://x.com/login"], allow))
+            .unwrap();
+        for p in &mut luna.programs {
+            p.normalization = Some(Normalization::Strict);
+        }
+        luna.reindex();
+        let conflicts = |luna: &Luna| -> Vec<String> {
+            luna.conflicts().iter().map(|c| c.to_string()).collect()
+        };
+        assert_eq!(conflicts(&luna), vec!["x.com: a, b, c"]);
+
+        luna.program_by_name("b").unwrap().normalization = Some(Normalization::Heuristic);
+        luna.reindex();
+        assert_eq!(
+            conflicts(&luna),
+            vec![
+                "https://x.com/tag/1/feed = https://x.com/tag/2/feed: a, b",
+                "x.com: a, b, c"
+            ]
+        );
+    }
 }
@@ -0,0 +1,197 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+// Values of the fields for each result, with program and asset context
+pub struct Table {
+    pub names: Vec<String>,
+    pub lists: Vec<bool>,
+    pub rows: Vec<Vec<Vec<String>>>,
+}
+
+impl Table {
+    fn record(&self, row: &[Vec<String>]) -> serde_json::Value {
+        let record = self
+            .names
+            .iter()
+            .zip(&self.lists)
+            .zip(row)
+            .map(|((name, &list), values)| {
+                let value = if list {
+                    serde_json::json!(values)
+                } else {
+                    values
+                        .first()
+                        .map_or(serde_json::Value::Null, |v| v.as_str().into())
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        serde_json::Value::Object(record)
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn tsv_escape(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+impl Format {
+    pub fn render(&self, table: &Table) -> Vec<String> {
+        let line = |row: &[Vec<String>], sep: &str, escape: fn(&str) -> String| {
+            row.iter()
+                .map(|values| escape(&values.join(",")))
+                .collect::<Vec<String>>()
+                .join(sep)
+        };
+        let header = |sep: &str, escape: fn(&str) -> String| {
+            table
+                .names
+                .iter()
+                .map(|n| escape(n))
+                .collect::<Vec<String>>()
+                .join(sep)
+        };
+
+        match self {
+            Format::Text => table
+                .rows
+                .iter()
+                .map(|r| line(r, "\t", |s| s.to_string()))
+                .collect(),
+            Format::Json => {
+                vec![
+                    serde_json::Value::Array(table.rows.iter().map(|r| table.record(r)).collect())
+                        .to_string(),
+                ]
+            }
+            Format::Jsonl => table
+                .rows
+                .iter()
+                .map(|r| table.record(r).to_string())
+                .collect(),
+            Format::Csv => std::iter::once(header(",", csv_escape))
+                .chain(table.rows.iter().map(|r| line(r, ",", csv_escape)))
+                .collect(),
+            Format::Tsv => std::iter::once(header("\t", tsv_escape))
+                .chain(table.rows.iter().map(|r| line(r, "\t", tsv_escape)))
+                .collect(),
+        }
+    }
+}
+
+// {url} {sc}
+pub struct Template {
+    text: String,
+    pub fields: Fields,
+}
+
+impl FromStr for Template {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = s
+            .split('{')
+            .skip(1)
+            .map(|p| {
+                p.split_once('}')
+                    .map(|(name, _)| name)
+                    .ok_or_else(|| format!("Unclosed field in template: {s}"))
+            })
+            .collect::<Result<Vec<&str>, String>>()?;
+
+        Ok(Self {
+            text: s.to_string(),
+            fields: Fields::from_str(&names.join(","))?,
+        })
+    }
+}
+
+impl Template {
+    pub fn render(&self, table: &Table) -> Vec<String> {
+        table
+            .rows
+            .iter()
+            .map(|row| {
+                table
+                    .names
+                    .iter()
+                    .zip(row)
+                    .fold(self.text.clone(), |text, (name, values)| {
+                        text.replace(&format!("{{{name}}}"), &values.join(","))
+                    })
+            })
+            .collect()
+    }
+}
+
+mod test {
+
+    #[test]
+    fn render() {
+        use super::*;
+
+        let table = Table {
+            names: vec!["asset".to_string(), "sc".to_string(), "tag".to_string()],
+            lists: vec![false, false, true],
+            rows: vec![
+                vec![
+                    vec!["https://a.com/".to_string()],
+                    vec![],
+                    vec!["xss".to_string(), "waf".to_string()],
+                ],
+                vec![
+                    vec!["https://b.com/".to_string()],
+                    vec!["200".to_string()],
+                    vec![],
+                ],
+            ],
+        };
+
+        assert_eq!(
+            Format::Jsonl.render(&table),
+            vec![
+                r#"{"asset":"https://a.com/","sc":null,"tag":["xss","waf"]}"#,
+                r#"{"asset":"https://b.com/","sc":"200","tag":[]}"#
+            ]
+        );
+        assert_eq!(
+            Format::Csv.render(&table),
+            vec![
+                "asset,sc,tag",
+                "https://a.com/,,\"xss,waf\"",
+                "https://b.com/,200,"
+            ]
+        );
+        assert_eq!(
+            Format::Tsv.render(&table)[1],
+            "https://a.com/\t\txss,waf".to_string()
+        );
+
+        let template = Template::from_str("{asset} [{sc}]").unwrap();
+        assert_eq!(
+            template.fields.keys(),
+            vec![query::Key::Asset, query::Key::Sc]
+        );
+        assert_eq!(
+            template.render(&table),
+            vec!["https://a.com/ []", "https://b.com/ [200]"]
+        );
+        assert!(Template::from_str("{asset").is_err());
+        assert!(Template::from_str("{foo}").is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use super::*;
+use serde_json::Value as Json;
+
+/// Whether a program accepts reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    Open,
+    Paused,
+    Closed,
+    /// Free text of older files that isn't a known state
+    Other(String),
+}
+
+impl State {
+    pub fn as_str(&self) -> &str {
+        match self {
+            State::Open => "open",
+            State::Paused => "paused",
+            State::Closed => "closed",
+            State::Other(text) => text,
+        }
+    }
+}
+
+impl FromStr for State {
+    type Err = Errors;
+
+    // Names used by the platforms too, e.g. soft_launched or disabled
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "open" | "active" | "public" | "soft_launched" => State::Open,
+            "paused" | "suspended" | "hold" => State::Paused,
+            "closed" | "disabled" | "inactive" | "archived" => State::Closed,
+            _ => return Err(format!("Unknown state: {s}").into()),
+        })
+    }
+}
+
+impl Serialize for State {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// Unknown ones are kept as they are
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(State::from_str(&s).unwrap_or(State::Other(s)))
+    }
+}
+
+/// Bounty range of a severity, or of any severity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reward {
+    pub severity: Option<Severity>,
+    pub min: u64,
+    pub max: u64,
+    /// Free text of older files that isn't a range, without amounts
+    pub text: Option<String>,
+}
+
+impl Reward {
+    pub fn is_range(&self) -> bool {
+        self.text.is_none()
+    }
+}
+
+impl FromStr for Reward {
+    type Err = Errors;
+
+    // high:500-1000, critical:5000, $100 - $5,000, $1k-$1.5m
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (severity, range) = match s.split_once(':') {
+            Some((sv, range)) => (Some(Severity::from_str(sv)?), range),
+            None => (None, s),
+        };
+        let amount = |s: &str| {
+            let invalid = || format!("Invalid bounty: {s}");
+            let end = s.rfind(|c: char| c.is_ascii_digit()).ok_or_else(invalid)? + 1;
+            let unit = match s[end..].trim_start().chars().next() {
+                Some('k' | 'K') => 1_000.0,
+                Some('m' | 'M') => 1_000_000.0,
+                _ => 1.0,
+            };
+            s[..end]
+                .chars()
+                .filter(|c| c.is_ascii_digit() || *c == '.')
+                .collect::<String>()
+                .parse::<f64>()
+                .map(|n| (n * unit).round() as u64)
+                .map_err(|_| invalid())
+        };
+        let (min, max) = match range.split_once('-') {
+            Some((min, max)) => (amount(min)?, amount(max)?),
+            None => (amount(range)?, amount(range)?),
+        };
+        if min > max {
+            return Err(format!("Invalid bounty range: {s}").into());
+        }
+        Ok(Reward {
+            severity,
+            min,
+            max,
+            text: None,
+        })
+    }
+}
+
+impl Display for Reward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(text) = &self.text {
+            return write!(f, "{}", text);
+        }
+        if let Some(severity) = &self.severity {
+            write!(f, "{}:", severity)?;
+        }
+        if self.min == self.max {
+            write!(f, "{}", self.max)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+impl Serialize for Reward {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Reward {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Reward::from_str(&s).unwrap_or(Reward {
+            severity: None,
+            min: 0,
+            max: 0,
+            text: Some(s),
+        }))
+    }
+}
+
+// Older files have a free text bounty, kept as a range if it is one
+pub(crate) fn rewards<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<Reward>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Text(Option<Reward>),
+        Table(Vec<Reward>),
+    }
+    Ok(match Stored::deserialize(d)? {
+        Stored::Text(reward) => reward
+            .filter(|r| r.text.as_deref().is_none_or(|t| !t.trim().is_empty()))
+            .into_iter()
+            .collect(),
+        Stored::Table(table) => table,
+    })
+}
+
+// hackerone.com -> hackerone
+fn platform(url: &str) -> Option<String> {
+    let host = url::Url::parse(url).ok()?.host_str()?.to_lowercase();
+    let mut labels = host.rsplit('.').skip(1);
+    labels.next().map(String::from)
+}
+
+// Web and network targets, not apps, executables, hardware, code or other ones
+fn web(target: &Json) -> bool {
+    match ["asset_type", "type"]
+        .iter()
+        .find_map(|key| target.get(key).and_then(|t| t.as_str()))
+    {
+        Some(typ) => matches!(
+            typ.to_lowercase().replace('-', "_").as_str(),
+            "url"
+                | "wildcard"
+                | "cidr"
+                | "ip_address"
+                | "iprange"
+                | "ip_range"
+                | "website"
+                | "api"
+                | "web_application"
+                | "network"
+        ),
+        None => true,
+    }
+}
+
+// *.example.com -> example.com
+fn scope(target: &str) -> Option<Asset> {
+    let target = target
+        .trim()
+        .trim_start_matches("*.")
+        .trim_end_matches("/*");
+    if target.is_empty() || target.contains(char::is_whitespace) || target.contains('*') {
+        return None;
+    }
+    Asset::from_str(target).ok()
+}
+
+/// Programs of a public program list, in the bounty-targets-data format of
+/// hackerone, bugcrowd, intigriti, yeswehack and federacy
+pub fn bounty_targets(text: &str, platform_name: Option<&str>) -> Result<Vec<Program>, Errors> {
+    let list: Vec<Json> = serde_json::from_str(text)?;
+    let text = |v: &Json, key: &str| v.get(key).and_then(|s| s.as_str()).map(String::from);
+    let amount = |v: Option<&Json>| match v {
+        Some(Json::Object(o)) => o.get("value").and_then(|v| v.as_f64()),
+        Some(v) => v.as_f64(),
+        None => None,
+    };
+
+    let mut programs = vec![];
+    for entry in &list {
+        let name = match text(entry, "name") {
+            Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+            _ => continue,
+        };
+        let url = text(entry, "url");
+
+        let state = text(entry, "submission_state")
+            .or_else(|| text(entry, "status"))
+            .and_then(|s| <State as FromStr>::from_str(&s).ok())
+            .or_else(|| match entry.get("disabled").and_then(|d| d.as_bool()) {
+                Some(true) => Some(State::Closed),
+                Some(false) => Some(State::Open),
+                None => None,
+            });
+
+        let min = amount(entry.get("min_bounty"));
+        let max = amount(entry.get("max_bounty")).or(amount(entry.get("max_payout")));
+        let bounty = match (min, max) {
+            (_, Some(max)) if max > 0.0 => vec![Reward {
+                severity: None,
+                min: min.unwrap_or_default() as u64,
+                max: max as u64,
+                text: None,
+            }],
+            _ => vec![],
+        };
+
+        let offers = ["offers_bounties", "offers_awards"]
+            .iter()
+            .any(|key| entry.get(key).and_then(|o| o.as_bool()) == Some(true));
+
+        let assets = entry
+            .pointer("/targets/in_scope")
+            .and_then(|s| s.as_array())
+            .into_iter()
+            .flatten()
+            .filter(|t| t.get("eligible_for_submission").and_then(|e| e.as_bool()) != Some(false))
+            .filter(|t| web(t))
+            .filter_map(|t| {
+                ["asset_identifier", "target", "endpoint"]
+                    .iter()
+                    .find_map(|key| text(t, key))
+            })
+            .flat_map(|t| t.split(',').filter_map(scope).collect::<Vec<Asset>>())
+            .collect();
+
+        programs.push(Program {
+            name,
+            platform: platform_name
+                .map(String::from)
+                .or_else(|| url.as_deref().and_then(platform)),
+            handle: text(entry, "handle"),
+            typ: Some(
+                if offers || !bounty.is_empty() {
+                    "bounty"
+                } else {
+                    "vdp"
+                }
+                .to_string(),
+            ),
+            url,
+            bounty,
+            state,
+            assets,
+            ..Default::default()
+        });
+    }
+    Ok(programs)
+}
+
+mod test {
+    #[test]
+    fn bounty_targets() {
+        use super::*;
+
+        let text = r#"[
+            {
+                "name": "Acme",
+                "url": "https://hackerone.com/acme",
+                "handle": "acme",
+                "offers_bounties": true,
+                "submission_state": "paused",
+                "targets": {
+                    "in_scope": [
+                        {"asset_identifier": "*.acme.com", "asset_type": "WILDCARD", "eligible_for_submission": true},
+                        {"asset_identifier": "api.acme.io,10.0.0.0/24"},
+                        {"asset_identifier": "com.acme.app", "asset_type": "GOOGLE_PLAY_APP_ID"},
+                        {"asset_identifier": "com.acme.ios", "asset_type": "APPLE_STORE_APP_ID"},
+                        {"asset_identifier": "acme.exe", "asset_type": "DOWNLOADABLE_EXECUTABLES"},
+                        {"asset_identifier": "router.acme", "asset_type": "HARDWARE"},
+                        {"asset_identifier": "github.com/acme", "asset_type": "SOURCE_CODE"},
+                        {"asset_identifier": "github.com", "asset_type": "OTHER"},
+                        {"asset_identifier": "shop.acme.com", "asset_type": "URL", "eligible_for_submission": false}
+                    ],
+                    "out_of_scope": [{"asset_identifier": "blog.acme.com"}]
+                }
+            },
+            {
+                "name": "Corp",
+                "url": "https://app.intigriti.com/programs/corp/corp",
+                "status": "open",
+                "min_bounty": {"value": 50, "currency": "EUR"},
+                "max_bounty": {"value": 5000, "currency": "EUR"},
+                "targets": {"in_scope": [
+                    {"endpoint": "https://corp.com/*", "type": "url"},
+                    {"endpoint": "corp.app", "type": "android"},
+                    {"endpoint": "10.1.0.0/16", "type": "iprange"}
+                ]}
+            },
+            {"name": ""}
+        ]"#;
+
+        let programs = super::bounty_targets(text, None).unwrap();
+        assert_eq!(programs.len(), 2);
+
+        let acme = &programs[0];
+        assert_eq!(acme.platform.as_deref(), Some("hackerone"));
+        assert_eq!(acme.state, Some(State::Paused));
+        assert_eq!(acme.typ.as_deref(), Some("bounty"));
+        let assets: Vec<String> = acme.assets.iter().map(|a| a.name.to_string()).collect();
+        assert_eq!(assets, vec!["acme.com", "api.acme.io", "10.0.0.0/24"]);
+
+        let corp = &programs[1];
+        assert_eq!(corp.platform.as_deref(), Some("intigriti"));
+        assert_eq!(corp.state, Some(State::Open));
+        let assets: Vec<String> = corp.assets.iter().map(|a| a.name.to_string()).collect();
+        assert_eq!(assets, vec!["https://corp.com/", "10.1.0.0/16"]);
+        assert_eq!(corp.bounty, vec![Reward::from_str("50-5000").unwrap()]);
+        assert_eq!(corp.max_bounty(), Some(5000));
+    }
+
+    #[test]
+    fn rewards() {
+        use super::*;
+
+        let reward = Reward::from_str("Critical:$1,000 - $5,000").unwrap();
+        assert_eq!(reward.severity, Some(Severity::Critical));
+        assert_eq!((reward.min, reward.max), (1000, 5000));
+        assert_eq!(reward.to_string(), "critical:1000-5000");
+        assert!(Reward::from_str("high:500-100").is_err());
+        assert!(Reward::from_str("swag").is_err());
+
+        let reward = Reward::from_str("$1k-$5k").unwrap();
+        assert_eq!((reward.min, reward.max), (1000, 5000));
+        let reward = Reward::from_str("high:1.5K - 2 M").unwrap();
+        assert_eq!((reward.min, reward.max), (1500, 2_000_000));
+        assert_eq!(Reward::from_str("500 USD").unwrap().max, 500);
+
+        // Free text of older files
+        let p: Program = serde_json::from_str(
+            r#"{"name":"x","bounty":"$100-$500","state":"ok","assets":[],"start":"2022-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(p.bounty, vec![Reward::from_str("100-500").unwrap()]);
+        assert_eq!(p.state, Some(State::Other("ok".to_string())));
+
+        let mut p = p;
+        p.bounty.push(Reward::from_str("critical:2000").unwrap());
+        assert_eq!(p.reward(&Severity::Critical).unwrap().max, 2000);
+        assert_eq!(p.reward(&Severity::Low).unwrap().max, 500);
+    }
+
+    #[test]
+    fn legacy() {
+        use super::*;
+
+        let file = r#"{
+            "name": "luna", "version": "0.9.0", "status": "", "start": "2022-01-01T00:00:00Z",
+            "programs": [
+                {"name":"a","bounty":"$1k-$5k","state":"open","assets":[],"start":"2022-01-01T00:00:00Z"},
+                {"name":"b","bounty":"Swag only","state":"invite only","notes":["vpn"],"assets":[],"start":"2022-01-01T00:00:00Z"}
+            ]
+        }"#;
+        let luna: Luna = serde_json::from_str(file).unwrap();
+        let saved = serde_json::to_string(&luna).unwrap();
+        let luna: Luna = serde_json::from_str(&saved).unwrap();
+
+        let a = &luna.programs[0];
+        assert_eq!(a.bounty, vec![Reward::from_str("1000-5000").unwrap()]);
+        assert_eq!(a.state, Some(State::Open));
+        assert!(a.notes.is_empty());
+
+        let b = &luna.programs[1];
+        assert_eq!(b.bounty.len(), 1);
+        assert!(!b.bounty[0].is_range());
+        assert_eq!(b.bounty[0].to_string(), "Swag only");
+        assert_eq!(b.max_bounty(), None);
+        assert_eq!(b.state, Some(State::Other("invite only".to_string())));
+        assert_eq!(b.notes, vec!["vpn"]);
+    }
+}
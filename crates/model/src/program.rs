@@ -1,5 +1,44 @@
 use super::*;
 
+/// What happens to assets that already belong to another program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Kept in the other program and tagged as shared with this one
+    #[default]
+    Shared,
+    /// Not inserted
+    Skip,
+    /// Inserted in both programs
+    Allow,
+}
+
+/// How CIDRs of a program are joined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    /// Joined with the tags and the first seen of their ranges
+    #[default]
+    Merge,
+    /// Only untagged ones are joined
+    Untagged,
+    /// Kept as they are
+    Off,
+}
+
+/// An asset in more than one program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub asset: String,
+    pub programs: Vec<String>,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.asset, self.programs.join(", "))
+    }
+}
+
 #[derive(Debug, Parser, Deserialize, Serialize, Default)]
 pub struct Program {
     pub name: String,
@@ -11,16 +50,54 @@ pub struct Program {
     pub typ: Option<String>,
     #[clap(long)]
     pub url: Option<String>,
-    #[clap(long)]
-    pub bounty: Option<String>,
-    #[clap(long)]
-    pub state: Option<String>,
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        help = "Bounty range of a severity, e.g. critical:5000 or high:500-1000"
+    )]
+    #[serde(
+        default,
+        deserialize_with = "platform::rewards",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub bounty: Vec<Reward>,
+    #[clap(long, help = "open, paused or closed")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    #[clap(long, help = "Launch date on the platform")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launched: Option<Time>,
+    #[clap(long, help = "Last change of the program on the platform")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<Time>,
+    #[clap(long = "note", multiple_occurrences = true)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    #[clap(long, arg_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<Normalization>,
+    #[clap(long = "on-wildcard", arg_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wildcard: Option<OnWildcard>,
+    #[clap(long = "on-conflict", arg_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<OnConflict>,
+    #[clap(long, arg_enum)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregation: Option<Aggregation>,
 
     #[clap(long, short, multiple_values = true)]
     pub assets: Vec<Asset>,
 
     #[clap(skip)]
     pub start: Time,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<Time>,
+
+    #[clap(skip)]
+    #[serde(skip)]
+    pub index: Index,
 }
 
 impl FromStr for Program {
@@ -37,136 +114,213 @@ impl FromStr for Program {
 impl Program {
     pub fn merge(&mut self, other: Self) {
         let new = self.start < other.start;
+        let seen = self.seen().max(other.seen());
 
         merge(&mut self.platform, other.platform, new);
         merge(&mut self.handle, other.handle, new);
         merge(&mut self.typ, other.typ, new);
         merge(&mut self.url, other.url, new);
-        merge(&mut self.bounty, other.bounty, new);
         merge(&mut self.state, other.state, new);
+        merge(&mut self.launched, other.launched, new);
+        merge(&mut self.updated, other.updated, new);
+        merge(&mut self.normalization, other.normalization, new);
+        merge(&mut self.wildcard, other.wildcard, new);
+        merge(&mut self.conflict, other.conflict, new);
+        merge(&mut self.aggregation, other.aggregation, new);
+
+        // The newer table replaces the whole older one
+        if self.bounty.is_empty() || new && !other.bounty.is_empty() {
+            self.bounty = other.bounty;
+        }
+        for note in other.notes {
+            if !self.notes.contains(&note) {
+                self.notes.push(note);
+            }
+        }
 
         self.start = self.start.min(other.start);
+        self.update = (seen > self.start).then_some(seen);
 
+        // Keys of the merged policy
+        self.reindex();
         for asset in other.assets {
             self.insert_asset(asset);
         }
     }
 
-    pub fn assets_search(&self, asset: &Asset) -> Result<usize, usize> {
-        if let AssetName::Url(s_req) = &asset.name {
-            // Path size
-            let s = s_req.url.path_segments().map_or(0, |s| {
-                s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                    .count()
-            });
+    // Last seen
+    pub fn seen(&self) -> Time {
+        self.update.unwrap_or(self.start)
+    }
 
-            // Index of 's' sized
-            let a = self.assets.partition_point(|a| {
-                if let AssetName::Url(req) = &a.name {
-                    let size = req.url.path_segments().map_or(0, |s| {
-                        s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                            .count()
-                    });
-                    req.url[..url::Position::BeforePath] < s_req.url[..url::Position::BeforePath]
-                        || (req.url[..url::Position::BeforePath]
-                            == s_req.url[..url::Position::BeforePath]
-                            && size < s)
-                } else {
-                    a < asset
-                }
-            });
+    pub fn normalization(&self) -> Normalization {
+        self.normalization.unwrap_or_default()
+    }
 
-            // Index of 's' sized
-            let b = self.assets.partition_point(|a| {
-                if let AssetName::Url(req) = &a.name {
-                    let size = req.url.path_segments().map_or(0, |s| {
-                        s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                            .count()
-                    });
-                    req.url[..url::Position::BeforePath] < s_req.url[..url::Position::BeforePath]
-                        || (req.url[..url::Position::BeforePath]
-                            == s_req.url[..url::Position::BeforePath]
-                            && size < s + 1)
-                } else {
-                    a < asset
-                }
-            });
+    pub fn on_wildcard(&self) -> OnWildcard {
+        self.wildcard.unwrap_or_default()
+    }
 
-            // find x in a..b
-            for x in a..b {
-                if &self.assets[x] == asset {
-                    return Ok(x);
-                }
-            }
+    pub fn on_conflict(&self) -> OnConflict {
+        self.conflict.unwrap_or_default()
+    }
+
+    pub fn aggregation(&self) -> Aggregation {
+        self.aggregation.unwrap_or_default()
+    }
+
+    // Most paid for any severity
+    pub fn max_bounty(&self) -> Option<u64> {
+        self.bounty
+            .iter()
+            .filter(|r| r.is_range())
+            .map(|r| r.max)
+            .max()
+    }
+
+    // Range of the severity, or of any severity if it has none
+    pub fn reward(&self, severity: &Severity) -> Option<&Reward> {
+        let mut ranges = self.bounty.iter().filter(|r| r.is_range());
+        ranges
+            .clone()
+            .find(|r| r.severity.as_ref() == Some(severity))
+            .or_else(|| ranges.find(|r| r.severity.is_none()))
+    }
+
+    fn bounty_table(&self) -> String {
+        self.bounty
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    // Reinsert all assets so urls get merged by the current policy
+    pub fn normalize(&mut self) -> usize {
+        let before = self.assets.len();
+        for asset in self.take_assets() {
+            self.insert_asset(asset);
         }
+        before.saturating_sub(self.assets.len())
+    }
 
-        self.assets.binary_search(asset)
+    /// Index keys of an asset name
+    pub fn keys(&self, name: &AssetName) -> Vec<String> {
+        match name {
+            AssetName::Url(req) => self.normalization().keys(req),
+            name => vec![name.to_string()],
+        }
     }
 
-    pub fn insert_asset(&mut self, asset: Asset) -> u8 {
-        match self.assets_search(&asset) {
-            Ok(i) => {
-                self.assets[i].merge(asset);
-                0
-            }
-            Err(i) => {
-                let ret = match asset.name {
-                    AssetName::Url(ref req) => {
-                        if let Some(host) = req.url.host() {
-                            if let Ok(host) = Asset::from_str(&host.to_string()) {
-                                self.insert_asset(host)
-                            } else {
-                                0
-                            }
-                        } else {
-                            0
-                        }
-                    }
-
-                    AssetName::Subdomain(_) => {
-                        if let Some(domain) = asset.name.domain() {
-                            let domain = Asset {
-                                name: domain,
-                                tags: vec![],
-                                start: time::Time::default(),
-                            };
-
-                            self.insert_asset(domain)
-                        } else {
-                            0
-                        }
-                    }
-                    _ => 0,
-                };
-                self.assets.insert(i + ret as usize, asset);
-                1 + ret
+    // Must be called after changing `assets` or `normalization` directly
+    pub fn reindex(&mut self) {
+        let mut index = Index::default();
+        for (i, asset) in self.assets.iter().enumerate() {
+            for key in self.keys(&asset.name) {
+                index.insert(key, i);
             }
         }
+        self.index = index;
     }
 
-    // Aggregate CIDRs
-    pub fn aggregate(&mut self) {
-        let nets = self
-            .assets
+    pub fn take_assets(&mut self) -> Vec<Asset> {
+        self.index.clear();
+        std::mem::take(&mut self.assets)
+    }
+
+    pub fn asset_position(&self, name: &AssetName) -> Option<usize> {
+        let policy = self.normalization();
+
+        // The first one if more than one are equal
+        self.keys(name)
             .iter()
-            .filter_map(|a| match a.name {
-                AssetName::Cidr(c) => Some(c),
-                _ => None,
+            .flat_map(|key| self.index.get(key))
+            .copied()
+            .filter(|&i| match (&self.assets[i].name, name) {
+                (AssetName::Url(a), AssetName::Url(b)) => policy.eq(a, b),
+                (a, b) => a == b,
             })
-            .collect();
+            .min()
+    }
 
-        self.assets
-            .retain(|a| !matches!(a.name, AssetName::Cidr(_)));
+    pub fn asset_by_name(&mut self, name: &AssetName) -> Option<&mut Asset> {
+        self.asset_position(name).map(|i| &mut self.assets[i])
+    }
 
-        let nets = IpNet::aggregate(&nets);
+    // Returns number of new assets
+    pub fn insert_asset(&mut self, asset: Asset) -> usize {
+        if let Some(i) = self.asset_position(&asset.name) {
+            self.assets[i].merge(asset);
+            return 0;
+        }
 
-        for n in nets {
-            self.assets.push(Asset {
-                name: AssetName::Cidr(n),
+        let parent = match &asset.name {
+            AssetName::Url(req) => req
+                .url
+                .host()
+                .and_then(|host| Asset::from_str(&host.to_string()).ok()),
+            AssetName::Subdomain(_) => asset.name.domain().map(|domain| Asset {
+                name: domain,
                 tags: vec![],
                 start: time::Time::default(),
+                update: None,
+                dns: None,
+                wildcard: None,
+            }),
+            _ => None,
+        };
+        let ret = parent.map_or(0, |parent| self.insert_asset(parent));
+
+        for key in self.keys(&asset.name) {
+            self.index.insert(key, self.assets.len());
+        }
+        self.assets.push(asset);
+        1 + ret
+    }
+
+    // Aggregate CIDRs, each range is merged into the one that contains it
+    pub fn aggregate(&mut self) {
+        let policy = self.aggregation();
+        if policy == Aggregation::Off {
+            return;
+        }
+
+        let (cidrs, mut assets): (Vec<Asset>, Vec<Asset>) =
+            self.take_assets().into_iter().partition(|a| match a.name {
+                AssetName::Cidr(_) => policy == Aggregation::Merge || a.tags.is_empty(),
+                _ => false,
             });
+
+        let nets = IpNet::aggregate(
+            &cidrs
+                .iter()
+                .filter_map(|a| match a.name {
+                    AssetName::Cidr(c) => Some(c),
+                    _ => None,
+                })
+                .collect(),
+        );
+
+        let mut aggregated: Vec<Option<Asset>> = nets.iter().map(|_| None).collect();
+        for mut asset in cidrs {
+            let i = match asset.name {
+                AssetName::Cidr(c) => nets.iter().position(|n| n.contains(&c)),
+                _ => None,
+            };
+            // Aggregated ranges always contain their parts
+            let Some(i) = i else { continue };
+            match &mut aggregated[i] {
+                Some(a) => a.merge(asset),
+                None => {
+                    asset.name = AssetName::Cidr(nets[i]);
+                    aggregated[i] = Some(asset);
+                }
+            }
         }
+        assets.extend(aggregated.into_iter().flatten());
+
+        self.assets = assets;
+        self.reindex();
     }
 
     pub fn assets(&self, field: Field, filter: &Filter) -> Vec<&Asset> {
@@ -183,8 +337,8 @@ impl Program {
                 )
             })
             .filter(|a| filter.asset(a))
-            .filter(|a| filter.start.map_or(true, |t| t < a.start))
-            .take(filter.n)
+            .filter(|a| filter.time(a.start, a.seen()))
+            .take(filter.n())
             .collect()
     }
 
@@ -199,6 +353,9 @@ impl Program {
     Handle:   {}
     Bounty:   {}
     State:    {}
+    Launched: {}
+    Updated:  {}
+    Notes:    [{}{}
     Assets:   {}
     Domains:  {}
     CIDRs:    {}
@@ -212,8 +369,22 @@ impl Program {
                 self.platform.as_ref().map_or("", |s| s),
                 self.typ.as_ref().map_or("", |s| s),
                 self.handle.as_ref().map_or("", |s| s),
-                self.bounty.as_ref().map_or("", |s| s),
-                self.state.as_ref().map_or("", |s| s),
+                self.bounty_table(),
+                self.state.as_ref().map_or("", |s| s.as_str()),
+                self.launched
+                    .map_or("".to_string(), |t| t.0.format("%Y-%m-%d").to_string()),
+                self.updated
+                    .map_or("".to_string(), |t| t.0.format("%Y-%m-%d").to_string()),
+                self.notes
+                    .iter()
+                    .map(|s| format!("\n        {}", s))
+                    .collect::<Vec<String>>()
+                    .join(""),
+                if self.notes.is_empty() {
+                    "]"
+                } else {
+                    "\n    ]"
+                },
                 self.assets.len(),
                 self.assets(Field::Domain, &Filter::default()).len(),
                 self.assets(Field::Cidr, &Filter::default()).len(),
@@ -232,6 +403,9 @@ impl Program {
     Handle:   {}
     Bounty:   {}
     State:    {}
+    Launched: {}
+    Updated:  {}
+    Notes:    [{}{}
     Assets:   {}
     Domains:  [{}{}
     CIDRs:    [{}{}
@@ -245,8 +419,22 @@ impl Program {
                 self.platform.as_ref().map_or("", |s| s),
                 self.typ.as_ref().map_or("", |s| s),
                 self.handle.as_ref().map_or("", |s| s),
-                self.bounty.as_ref().map_or("", |s| s),
-                self.state.as_ref().map_or("", |s| s),
+                self.bounty_table(),
+                self.state.as_ref().map_or("", |s| s.as_str()),
+                self.launched
+                    .map_or("".to_string(), |t| t.0.format("%Y-%m-%d").to_string()),
+                self.updated
+                    .map_or("".to_string(), |t| t.0.format("%Y-%m-%d").to_string()),
+                self.notes
+                    .iter()
+                    .map(|s| format!("\n        {}", s))
+                    .collect::<Vec<String>>()
+                    .join(""),
+                if self.notes.is_empty() {
+                    "]"
+                } else {
+                    "\n    ]"
+                },
                 self.assets.len(),
                 self.assets(Field::Domain, &Filter::default())
                     .iter()
@@ -283,94 +471,49 @@ impl Program {
 
 #[cfg(test)]
 mod test {
-
-    #[test]
-    fn partition_point_test() {
+    // Sorted assets of the program, urls compared by the program policy as
+    // equality of requests is strict
+    fn same(p: &super::Program, expected: Vec<super::Asset>) -> bool {
         use super::*;
 
-        let r = Request::from_str("http://w.com/f/c").unwrap();
-        let assets = vec![
-            Asset::from_str("http://w.com/a").unwrap(),
-            Asset::from_str("http://w.com/a/b/").unwrap(),
-            Asset::from_str("http://w.com/a/b/c").unwrap(),
-            Asset::from_str("http://w.com/a/b/c/d").unwrap(),
-            Asset::from_str("http://x.com/a/").unwrap(),
-            Asset::from_str("http://x.com/a/b").unwrap(),
-            Asset::from_str("http://x.com/a/b/c").unwrap(),
-            Asset::from_str("http://y.com/a/").unwrap(),
-            Asset::from_str("http://y.com/a/b").unwrap(),
-            Asset::from_str("http://y.com/a/b/c").unwrap(),
-            Asset::from_str("http://y.com/a/b/c/d/e").unwrap(),
-            Asset::from_str("http://y.com/a/b/c/d/e").unwrap(),
-            Asset::from_str("http://y.com/a/b/c/d/e").unwrap(),
-        ];
-
-        let a = assets.partition_point(|a| {
-            if let AssetName::Url(req) = &a.name {
-                let size = req.url.path_segments().map_or(0, |s| {
-                    s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                        .count()
-                });
-
-                req.url[..url::Position::BeforePath] < r.url[..url::Position::BeforePath]
-                    || (req.url[..url::Position::BeforePath] == r.url[..url::Position::BeforePath]
-                        && size < 2)
-            } else {
-                true
-            }
-        });
-
-        println!("{a}");
+        let policy = p.normalization();
+        let eq = |a: &Asset, b: &Asset| match (&a.name, &b.name) {
+            (AssetName::Url(a), AssetName::Url(b)) => policy.eq(a, b),
+            (a, b) => a == b,
+        };
+        let mut assets: Vec<&Asset> = p.assets.iter().collect();
+        assets.sort();
+        assets.len() == expected.len() && assets.iter().zip(&expected).all(|(a, e)| eq(a, e))
     }
 
     #[test]
-    fn partition_point_test_1() {
+    fn asset_position() {
         use super::*;
 
-        let r = Asset::from_str("d.com").unwrap();
-        let assets = vec![
-            Asset::from_str("a.com").unwrap(),
-            Asset::from_str("b.com").unwrap(),
-            Asset::from_str("c.com").unwrap(),
-            Asset::from_str("e.com").unwrap(),
-            Asset::from_str("http://w.com/a").unwrap(),
-            Asset::from_str("http://w.com/a/b/").unwrap(),
-            Asset::from_str("http://w.com/a/b/c").unwrap(),
-            Asset::from_str("http://w.com/a/b/c/d").unwrap(),
-            Asset::from_str("http://x.com/a/").unwrap(),
-            Asset::from_str("http://x.com/a/b").unwrap(),
-            Asset::from_str("http://x.com/a/b/c").unwrap(),
-            Asset::from_str("http://x.com/a/b/c").unwrap(),
-            Asset::from_str("http://x.com/a/b/c").unwrap(),
-            Asset::from_str("http://x.com/a/b/c/d/e").unwrap(),
-        ];
+        let mut p = Program::default();
+        p.insert_asset(Asset::from_str("http://sub.x.com/a/b").unwrap());
+        p.insert_asset(Asset::from_str("10.0.0.0/24").unwrap());
 
-        let a = assets.partition_point(|a| {
-            if let (AssetName::Url(req), AssetName::Url(r)) = (&a.name, &r.name) {
-                let size = req.url.path_segments().map_or(0, |s| {
-                    s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                        .count()
-                });
-
-                req.url[..url::Position::BeforePath] < r.url[..url::Position::BeforePath]
-                    || (req.url[..url::Position::BeforePath] == r.url[..url::Position::BeforePath]
-                        && size < 2)
-            } else {
-                a < &r
-            }
-        });
+        let position = |p: &Program, s: &str| p.asset_position(&AssetName::from_str(s).unwrap());
 
-        assert_eq!(a, 3);
+        assert_eq!(position(&p, "x.com"), Some(0));
+        assert_eq!(position(&p, "sub.x.com"), Some(1));
+        assert_eq!(position(&p, "http://sub.x.com/a/c"), Some(2));
+        assert_eq!(position(&p, "10.0.0.0/24"), Some(3));
+        assert_eq!(position(&p, "http://sub.x.com/a"), None);
+        assert_eq!(position(&p, "https://sub.x.com/a/b"), None);
+        assert_eq!(position(&p, "y.com"), None);
     }
 
     #[test]
     fn assets_search_test_1() {
         use super::*;
 
-        assert!(
-            Asset::from_str("http://x.com/b/a").unwrap()
-                == Asset::from_str("http://x.com/g/a").unwrap()
-        );
+        // Equal under the heuristic policy only, requests themselves are strict
+        let a = Request::from_str("http://x.com/b/a").unwrap();
+        let b = Request::from_str("http://x.com/g/a").unwrap();
+        assert!(Normalization::Heuristic.eq(&a, &b));
+        assert_ne!(a, b);
 
         let assets = vec![
             Asset::from_str("http://x.com/a").unwrap(),
@@ -402,18 +545,12 @@ mod test {
             println!("{}", a.name);
         }
 
-        assert_eq!(
-            p2.assets_search(&Asset::from_str("http://x.com/a/").unwrap()),
-            Ok(1)
-        );
-        assert_eq!(
-            p2.assets_search(&Asset::from_str("http://x.com/g/a/").unwrap()),
-            Ok(3)
-        );
-        assert_eq!(
-            p2.assets_search(&Asset::from_str("http://x.com/g/a/b").unwrap()),
-            Ok(6)
-        );
+        let position = |s: &str| p2.asset_position(&AssetName::from_str(s).unwrap());
+
+        assert_eq!(p2.assets.len(), 11);
+        assert_eq!(position("http://x.com/a/"), Some(1));
+        assert_eq!(position("http://x.com/g/a/"), Some(3));
+        assert_eq!(position("http://x.com/g/a/b"), Some(6));
     }
 
     #[test]
@@ -430,25 +567,18 @@ mod test {
             Asset::from_str("http://x.com/a/b/c/d/e").unwrap(),
         ];
 
-        let p1 = Program {
+        let mut p1 = Program {
             name: "p1".to_string(),
             assets,
             ..Default::default()
         };
+        p1.reindex();
 
-        assert_eq!(
-            p1.assets_search(&Asset::from_str("http://x.com/a/b/").unwrap()),
-            Ok(1)
-        );
-        assert_eq!(
-            p1.assets_search(&Asset::from_str("http://x.com/a/b/c/d/e").unwrap()),
-            Ok(6)
-        );
+        let position = |s: &str| p1.asset_position(&AssetName::from_str(s).unwrap());
 
-        assert_eq!(
-            p1.assets_search(&Asset::from_str("http://x.com/a/b/c/d/").unwrap()),
-            Err(6)
-        );
+        assert_eq!(position("http://x.com/a/b/"), Some(1));
+        assert_eq!(position("http://x.com/a/b/c/d/e"), Some(6));
+        assert_eq!(position("http://x.com/a/b/c/d/"), None);
     }
 
     #[test]
@@ -482,13 +612,13 @@ mod test {
             println!("{}", a.name);
         }
 
-        assert_eq!(
-            Asset::from_str("http://sub.x.com/api/history-media/?format=json&loc=16&lang=de&pi=6&q=ambasador&feed=t&co=de").unwrap(),
-            Asset::from_str("http://sub.x.com/api/history-media/?loc=16&pi=6&feed=t&lang=de&format=json&q=ambasador&co=de").unwrap(),
-        );
+        assert!(Normalization::Heuristic.eq(
+            &Request::from_str("http://sub.x.com/api/history-media/?format=json&loc=16&lang=de&pi=6&q=ambasador&feed=t&co=de").unwrap(),
+            &Request::from_str("http://sub.x.com/api/history-media/?loc=16&pi=6&feed=t&lang=de&format=json&q=ambasador&co=de").unwrap(),
+        ));
 
-        assert_eq!(
-            p2.assets,
+        assert!(same(
+            &p2,
             vec![
                 Asset::from_str("x.com").unwrap(),
                 Asset::from_str("sub.x.com").unwrap(),
@@ -500,7 +630,7 @@ mod test {
                 Asset::from_str("http://sub.x.com/at/en").unwrap(),
                 Asset::from_str("http://sub.x.com/be/nl").unwrap(),
             ],
-        );
+        ));
     }
 
     #[test]
@@ -531,8 +661,8 @@ mod test {
             println!("{}", a.name);
         }
 
-        assert_eq!(
-            p2.assets,
+        assert!(same(
+            &p2,
             vec![
                 Asset::from_str("x.com").unwrap(),
                 Asset::from_str("https://x.com/pregnancy/pregnancy-health/%20/lgbtq?").unwrap(),
@@ -540,6 +670,142 @@ mod test {
                 // Asset::from_str("https://x.com/pregnancy/pregnancy-lifestyle/%20/being-a-mom").unwrap(),
                 Asset::from_str("https://x.com/pregnancy/week-by-week/%20/being-a-mom?").unwrap(),
             ],
+        ));
+        // The first one is kept
+        assert_eq!(
+            p2.assets[3],
+            Asset::from_str("https://x.com/pregnancy/pregnancy-lifestyle/%20/being-a-mom").unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        use super::*;
+
+        let mut p = Program {
+            name: "p".to_string(),
+            ..Default::default()
+        };
+        p.insert_asset(Asset::from_str("https://x.com/tag/a/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/").unwrap());
+        assert_eq!(p.assets.len(), 2);
+
+        let mut p = Program {
+            name: "p".to_string(),
+            normalization: Some(Normalization::Strict),
+            ..Default::default()
+        };
+        p.insert_asset(Asset::from_str("https://x.com/tag/a/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/?id=2").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/?id=3").unwrap());
+        assert_eq!(p.assets.len(), 4);
+
+        p.normalization = Some(Normalization::Pattern);
+        p.insert_asset(Asset::from_str("https://x.com/tag/2/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/3/feed/").unwrap());
+        assert_eq!(p.assets.len(), 5);
+    }
+
+    // Heuristic equality isn't transitive, urls are merged in the order they were inserted
+    #[test]
+    fn normalize_order() {
+        use super::*;
+
+        let mut p = Program {
+            name: "p".to_string(),
+            normalization: Some(Normalization::Strict),
+            ..Default::default()
+        };
+        p.insert_asset(Asset::from_str("https://x.com/tag/a/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/?id=2").unwrap());
+        assert_eq!(p.assets.len(), 4);
+
+        // b joins a, but b?id=2 differs from a in a segment and in the query
+        p.normalization = Some(Normalization::Heuristic);
+        assert_eq!(p.normalize(), 1);
+        assert_eq!(
+            p.assets[1..],
+            [
+                Asset::from_str("https://x.com/tag/a/feed/").unwrap(),
+                Asset::from_str("https://x.com/tag/b/feed/?id=2").unwrap(),
+            ]
+        );
+
+        // In the other order all of them are one
+        let mut p = Program::default();
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/a/feed/").unwrap());
+        p.insert_asset(Asset::from_str("https://x.com/tag/b/feed/?id=2").unwrap());
+        assert_eq!(p.assets.len(), 2);
+    }
+
+    #[test]
+    fn merge_normalization() {
+        use super::*;
+
+        let mut p = Program::default();
+        p.insert_asset(Asset::from_str("https://y.com/a/b").unwrap());
+
+        let other = Program {
+            normalization: Some(Normalization::Pattern),
+            start: Time(Utc::now() + chrono::Duration::seconds(1)),
+            assets: vec![Asset::from_str("https://y.com/a/b").unwrap()],
+            ..Default::default()
+        };
+        p.merge(other);
+        assert_eq!(p.normalization(), Normalization::Pattern);
+        assert_eq!(p.assets.len(), 2);
+    }
+
+    #[test]
+    fn aggregate() {
+        use super::*;
+
+        let cidr = |s: &str, tag: Option<&str>, days: i64| {
+            let mut a = Asset::from_str(s).unwrap();
+            a.start = Time(Utc::now() - chrono::Duration::days(days));
+            a.tags = tag.map(|t| Tag::from_str(t).unwrap()).into_iter().collect();
+            a
+        };
+        let program = |aggregation| Program {
+            assets: vec![
+                Asset::from_str("x.com").unwrap(),
+                cidr("10.0.0.0/25", Some("cdn"), 3),
+                cidr("10.0.0.128/25", None, 7),
+                cidr("10.0.1.0/24", None, 1),
+            ],
+            aggregation,
+            ..Default::default()
+        };
+        let names =
+            |p: &Program| -> Vec<String> { p.assets.iter().map(|a| a.name.to_string()).collect() };
+
+        let mut p = program(None);
+        p.aggregate();
+        assert_eq!(names(&p), vec!["x.com", "10.0.0.0/23"]);
+        let net = &p.assets[1];
+        assert_eq!(net.tags.len(), 1);
+        assert!(net.start.0 < Utc::now() - chrono::Duration::days(6));
+        assert!(p
+            .asset_position(&AssetName::from_str("10.0.0.0/23").unwrap())
+            .is_some());
+
+        let mut p = program(Some(Aggregation::Untagged));
+        p.aggregate();
+        assert_eq!(
+            names(&p),
+            vec!["x.com", "10.0.0.0/25", "10.0.0.128/25", "10.0.1.0/24"]
         );
+
+        let mut p = program(Some(Aggregation::Untagged));
+        p.assets.push(cidr("10.0.0.0/24", None, 0));
+        p.aggregate();
+        assert_eq!(names(&p), vec!["x.com", "10.0.0.0/25", "10.0.0.0/23"]);
+
+        let mut p = program(Some(Aggregation::Off));
+        p.aggregate();
+        assert_eq!(p.assets.len(), 4);
     }
 }
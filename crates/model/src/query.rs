@@ -0,0 +1,599 @@
+use super::*;
+
+// sub ~ "api" and not tag = "waf" and sc in [200, 302]
+#[derive(Debug, Clone)]
+pub enum Query {
+    Const(bool),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Cmp(Key, Op),
+}
+
+// asset is any asset and domain, sub, url and cidr only the ones of that type,
+// while program_url is the url of the program, in queries and fields alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Program,
+    Platform,
+    Type,
+    ProgramUrl,
+    Handle,
+    Bounty,
+    State,
+    Asset,
+    Domain,
+    Sub,
+    Url,
+    Cidr,
+    Sc,
+    Title,
+    Resp,
+    Tag,
+    Severity,
+    Value,
+    Status,
+    Assignee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Program,
+    Asset,
+    Tag,
+}
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    Regex(regex::Regex),
+    Eq(String),
+    In(Vec<String>),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+}
+
+impl FromStr for Key {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "program" => Key::Program,
+            "platform" => Key::Platform,
+            "type" => Key::Type,
+            "program_url" => Key::ProgramUrl,
+            "handle" => Key::Handle,
+            "bounty" => Key::Bounty,
+            "state" => Key::State,
+            "asset" => Key::Asset,
+            "domain" => Key::Domain,
+            "sub" => Key::Sub,
+            "url" => Key::Url,
+            "cidr" => Key::Cidr,
+            "sc" => Key::Sc,
+            "title" => Key::Title,
+            "resp" => Key::Resp,
+            "tag" => Key::Tag,
+            "sv" | "severity" => Key::Severity,
+            "status" => Key::Status,
+            "assignee" => Key::Assignee,
+            "value" => Key::Value,
+            _ => return Err(format!("Unknown field: {s}").into()),
+        })
+    }
+}
+
+impl Key {
+    pub fn level(&self) -> Level {
+        match self {
+            Key::Program
+            | Key::Platform
+            | Key::Type
+            | Key::ProgramUrl
+            | Key::Handle
+            | Key::Bounty
+            | Key::State => Level::Program,
+            Key::Asset
+            | Key::Domain
+            | Key::Sub
+            | Key::Url
+            | Key::Cidr
+            | Key::Sc
+            | Key::Title
+            | Key::Resp => Level::Asset,
+            Key::Tag | Key::Severity | Key::Value | Key::Status | Key::Assignee => Level::Tag,
+        }
+    }
+
+    fn program(&self, p: &Program) -> Vec<String> {
+        match self {
+            Key::Program => vec![p.name.clone()],
+            Key::Platform => p.platform.clone().into_iter().collect(),
+            Key::Type => p.typ.clone().into_iter().collect(),
+            Key::ProgramUrl => p.url.clone().into_iter().collect(),
+            Key::Handle => p.handle.clone().into_iter().collect(),
+            Key::Bounty => p.bounty.iter().map(|r| r.to_string()).collect(),
+            Key::State => p.state.iter().map(|s| s.as_str().to_string()).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn asset(&self, a: &Asset) -> Vec<String> {
+        match (self, &a.name) {
+            (Key::Asset, name)
+            | (Key::Domain, name @ AssetName::Domain(_))
+            | (Key::Sub, name @ AssetName::Subdomain(_))
+            | (Key::Url, name @ AssetName::Url(_))
+            | (Key::Cidr, name @ AssetName::Cidr(_)) => vec![name.to_string()],
+            (Key::Sc, AssetName::Url(req)) => req.sc.clone().into_iter().collect(),
+            (Key::Title, AssetName::Url(req)) => req.title.clone().into_iter().collect(),
+            (Key::Resp, AssetName::Url(req)) => req.resp.clone().into_iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    fn tag<'a>(&self, t: &'a Tag) -> Vec<&'a str> {
+        match self {
+            Key::Tag => vec![t.name.as_str()],
+            Key::Severity => t.severity.iter().map(|s| s.as_str()).collect(),
+            Key::Value => t.values.iter().map(|v| v.value.as_str()).collect(),
+            Key::Status => vec![t.status().as_str()],
+            Key::Assignee => t.assignee.as_deref().into_iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    // Whether results of the level may have many values of the key
+    pub fn is_list(&self, level: Level) -> bool {
+        *self == Key::Value || self.level() > level
+    }
+
+    // Values in the context, gathered from the children if the key is of a lower level
+    pub fn values(&self, p: &Program, a: Option<&Asset>, t: Option<&Tag>) -> Vec<String> {
+        let tags = |a: &Asset| -> Vec<String> {
+            a.tags
+                .iter()
+                .flat_map(|t| self.tag(t))
+                .map(String::from)
+                .collect()
+        };
+        match (self.level(), a, t) {
+            (Level::Program, _, _) => self.program(p),
+            (Level::Asset, Some(a), _) => self.asset(a),
+            (Level::Asset, None, _) => p.assets.iter().flat_map(|a| self.asset(a)).collect(),
+            (Level::Tag, _, Some(t)) => self.tag(t).into_iter().map(String::from).collect(),
+            (Level::Tag, Some(a), None) => tags(a),
+            (Level::Tag, None, None) => p.assets.iter().flat_map(tags).collect(),
+        }
+    }
+}
+
+// url,sc,title,program
+#[derive(Debug, Clone)]
+pub struct Fields(pub Vec<(String, Key)>);
+
+impl FromStr for Fields {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|f| f.trim().to_lowercase())
+            .filter(|f| !f.is_empty())
+            .map(|f| Ok((f.clone(), Key::from_str(&f)?)))
+            .collect::<Result<Vec<(String, Key)>, Errors>>()
+            .map(Fields)
+    }
+}
+
+impl Fields {
+    pub fn of(field: Field) -> Self {
+        let fields = match field {
+            Field::Program => "program,platform,type,program_url,handle,bounty,state",
+            Field::Tag | Field::Value => "program,asset,tag,severity,value",
+            _ => "program,asset,sc,title,tag",
+        };
+        Self::from_str(fields).unwrap()
+    }
+
+    pub fn keys(&self) -> Vec<Key> {
+        self.0.iter().map(|(_, k)| *k).collect()
+    }
+}
+
+impl From<Field> for Level {
+    fn from(field: Field) -> Self {
+        match field {
+            Field::Luna | Field::Program | Field::None => Level::Program,
+            Field::Tag | Field::Value => Level::Tag,
+            _ => Level::Asset,
+        }
+    }
+}
+
+impl Op {
+    pub fn test(&self, s: &str) -> bool {
+        let num = || s.trim().parse::<f64>().ok();
+        match self {
+            Op::Regex(re) => re.is_match(s),
+            Op::Eq(v) => v.to_lowercase() == s.to_lowercase(),
+            Op::In(vs) => vs.iter().any(|v| v.to_lowercase() == s.to_lowercase()),
+            Op::Lt(n) => num().is_some_and(|s| s < *n),
+            Op::Le(n) => num().is_some_and(|s| s <= *n),
+            Op::Gt(n) => num().is_some_and(|s| s > *n),
+            Op::Ge(n) => num().is_some_and(|s| s >= *n),
+        }
+    }
+}
+
+impl Query {
+    // Leaves of a lower level than the given context match if any child matches
+    fn eval(&self, p: Option<&Program>, a: Option<&Asset>, t: Option<&Tag>) -> bool {
+        match self {
+            Query::Const(b) => *b,
+            Query::Not(q) => !q.eval(p, a, t),
+            Query::And(l, r) => l.eval(p, a, t) && r.eval(p, a, t),
+            Query::Or(l, r) => l.eval(p, a, t) || r.eval(p, a, t),
+            Query::Cmp(key, op) => {
+                let program = |p: &Program| match (key, op) {
+                    // Bounties are compared by their most paid amount
+                    (Key::Bounty, Op::Lt(_) | Op::Le(_) | Op::Gt(_) | Op::Ge(_)) => p
+                        .bounty
+                        .iter()
+                        .filter(|r| r.is_range())
+                        .any(|r| op.test(&r.max.to_string())),
+                    _ => key.program(p).iter().any(|s| op.test(s)),
+                };
+                let asset = |a: &Asset| key.asset(a).iter().any(|s| op.test(s));
+                let tag = |t: &Tag| match (key, op) {
+                    (Key::Severity, Op::Lt(_) | Op::Le(_) | Op::Gt(_) | Op::Ge(_)) => t
+                        .severity
+                        .as_ref()
+                        .is_some_and(|s| op.test(&s.rank().to_string())),
+                    _ => key.tag(t).into_iter().any(|s| op.test(s)),
+                };
+                let tags = |a: &Asset| a.tags.iter().any(tag);
+
+                match (key.level(), p, a, t) {
+                    (Level::Program, Some(p), _, _) => program(p),
+                    (Level::Asset, _, Some(a), _) => asset(a),
+                    (Level::Asset, Some(p), None, _) => p.assets.iter().any(asset),
+                    (Level::Tag, _, _, Some(t)) => tag(t),
+                    (Level::Tag, _, Some(a), None) => tags(a),
+                    (Level::Tag, Some(p), None, None) => p.assets.iter().any(tags),
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    // A program matches if one of its assets does
+    pub fn program(&self, p: &Program) -> bool {
+        if p.assets.is_empty() {
+            self.eval(Some(p), None, None)
+        } else {
+            let q = self.bind_program(p);
+            p.assets.iter().any(|a| q.asset(a))
+        }
+    }
+    pub fn asset(&self, a: &Asset) -> bool {
+        self.eval(None, Some(a), None)
+    }
+    pub fn tag(&self, t: &Tag) -> bool {
+        self.eval(None, None, Some(t))
+    }
+
+    // Replace leaves of the level with their result
+    fn bind(&self, level: Level, p: Option<&Program>, a: Option<&Asset>) -> Query {
+        match self {
+            Query::Not(q) => Query::Not(Box::new(q.bind(level, p, a))),
+            Query::And(l, r) => {
+                Query::And(Box::new(l.bind(level, p, a)), Box::new(r.bind(level, p, a)))
+            }
+            Query::Or(l, r) => {
+                Query::Or(Box::new(l.bind(level, p, a)), Box::new(r.bind(level, p, a)))
+            }
+            Query::Cmp(key, _) if key.level() == level => Query::Const(self.eval(p, a, None)),
+            q => q.clone(),
+        }
+    }
+
+    pub fn bind_program(&self, p: &Program) -> Query {
+        self.bind(Level::Program, Some(p), None)
+    }
+    pub fn bind_asset(&self, a: &Asset) -> Query {
+        self.bind(Level::Asset, None, Some(a))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(&'static str),
+    Open,
+    Close,
+    ListOpen,
+    ListClose,
+    Comma,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Errors> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '[' | ']' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    '[' => Token::ListOpen,
+                    ']' => Token::ListClose,
+                    _ => Token::Comma,
+                });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut str = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => str.extend(chars.next()),
+                        Some(n) if n == c => break,
+                        Some(n) => str.push(n),
+                        None => return Err(format!("Unclosed quote in: {s}").into()),
+                    }
+                }
+                tokens.push(Token::Str(str));
+            }
+            '~' | '=' | '!' | '<' | '>' | '&' | '|' => {
+                let two: String = chars.clone().take(2).collect();
+                let (op, len) = match (two.as_str(), c) {
+                    ("!~", _) => ("!~", 2),
+                    ("!=", _) => ("!=", 2),
+                    ("==", _) => ("=", 2),
+                    ("<=", _) => ("<=", 2),
+                    (">=", _) => (">=", 2),
+                    ("&&", _) => ("and", 2),
+                    ("||", _) => ("or", 2),
+                    (_, '~') => ("~", 1),
+                    (_, '=') => ("=", 1),
+                    (_, '<') => ("<", 1),
+                    (_, '>') => (">", 1),
+                    (_, '!') => ("not", 1),
+                    _ => return Err(format!("Invalid operator: {two}").into()),
+                };
+                chars.nth(len - 1);
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()[],~=!<>&|\"'".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.to_lowercase().as_str() {
+                    "and" => Token::Op("and"),
+                    "or" => Token::Op("or"),
+                    "not" => Token::Op("not"),
+                    "in" => Token::Op("in"),
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct QueryParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, Errors> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Op("or")) {
+            left = Query::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Query, Errors> {
+        let mut left = self.unary()?;
+        while self.eat(&Token::Op("and")) {
+            left = Query::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Query, Errors> {
+        if self.eat(&Token::Op("not")) {
+            Ok(Query::Not(Box::new(self.unary()?)))
+        } else if self.eat(&Token::Open) {
+            let query = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err("Expected \")\"".into());
+            }
+            Ok(query)
+        } else {
+            self.cmp()
+        }
+    }
+
+    fn value(&mut self) -> Result<String, Errors> {
+        match self.next() {
+            Some(Token::Word(s)) | Some(Token::Str(s)) => Ok(s),
+            t => Err(format!("Expected a value, found: {t:?}").into()),
+        }
+    }
+
+    // Severities are compared by rank
+    fn number(&mut self, key: Key) -> Result<f64, Errors> {
+        let value = self.value()?;
+        if key == Key::Severity {
+            return Ok(Severity::from_str(&value)?.rank() as f64);
+        }
+        value
+            .parse()
+            .map_err(|_| format!("Expected a number, found: {value}").into())
+    }
+
+    fn cmp(&mut self) -> Result<Query, Errors> {
+        let key = match self.next() {
+            Some(Token::Word(w)) => Key::from_str(&w)?,
+            t => return Err(format!("Expected a field, found: {t:?}").into()),
+        };
+
+        let (op, negate) = match self.next() {
+            Some(Token::Op("~")) => (Op::Regex(regex(&self.value()?)?), false),
+            Some(Token::Op("!~")) => (Op::Regex(regex(&self.value()?)?), true),
+            Some(Token::Op("=")) => (Op::Eq(self.value()?), false),
+            Some(Token::Op("!=")) => (Op::Eq(self.value()?), true),
+            Some(Token::Op("<")) => (Op::Lt(self.number(key)?), false),
+            Some(Token::Op("<=")) => (Op::Le(self.number(key)?), false),
+            Some(Token::Op(">")) => (Op::Gt(self.number(key)?), false),
+            Some(Token::Op(">=")) => (Op::Ge(self.number(key)?), false),
+            Some(Token::Op("in")) => {
+                if !self.eat(&Token::ListOpen) {
+                    return Err("Expected \"[\" after in".into());
+                }
+                let mut values = vec![];
+                while !self.eat(&Token::ListClose) {
+                    values.push(self.value()?);
+                    if !self.eat(&Token::Comma) && self.peek() != Some(&Token::ListClose) {
+                        return Err("Expected \",\" or \"]\"".into());
+                    }
+                }
+                (Op::In(values), false)
+            }
+            t => return Err(format!("Expected an operator, found: {t:?}").into()),
+        };
+
+        let query = Query::Cmp(key, op);
+        Ok(if negate {
+            Query::Not(Box::new(query))
+        } else {
+            query
+        })
+    }
+}
+
+fn regex(s: &str) -> Result<regex::Regex, Errors> {
+    Ok(regex::Regex::new(&format!("(?i){}", s))?)
+}
+
+impl FromStr for Query {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = QueryParser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+
+        let query = parser.or()?;
+        if let Some(t) = parser.peek() {
+            return Err(format!("Unexpected: {t:?}").into());
+        }
+        Ok(query)
+    }
+}
+
+mod test {
+
+    #[test]
+    fn parse() {
+        use super::*;
+
+        assert!(Query::from_str(r#"sub ~ "api" and not tag = "waf" and sc in [200,302]"#).is_ok());
+        assert!(Query::from_str("(sc = 200 || sc == 302) && !title ~ admin").is_ok());
+        assert!(Query::from_str("sc >= 400 and sc < 500").is_ok());
+        assert!(Query::from_str("sc in [200, 302,]").is_ok());
+
+        assert!(Query::from_str("sc in 200").is_err());
+        assert!(Query::from_str("color = red").is_err());
+        assert!(Query::from_str("(sc = 200").is_err());
+        assert!(Query::from_str("sc = \"200").is_err());
+        assert!(Query::from_str("sc = 200 sc = 302").is_err());
+    }
+
+    #[test]
+    fn eval() {
+        use super::*;
+
+        let mut program = Program::from_str("google").unwrap();
+        let mut api = Asset::from_str("https://api.google.com/v1").unwrap();
+        if let AssetName::Url(req) = &mut api.name {
+            req.sc = Some("302".to_string());
+        }
+        let mut cdn = Asset::from_str("cdn.google.com").unwrap();
+        cdn.tags.push(Tag::from_str("waf").unwrap());
+        program.insert_asset(api);
+        program.insert_asset(cdn);
+
+        let q = |s: &str| Query::from_str(s).unwrap();
+        let assets = |s: &str| {
+            let q = q(s).bind_program(&program);
+            program
+                .assets
+                .iter()
+                .filter(|a| q.asset(a))
+                .map(|a| a.name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(q("program = GOOGLE").program(&program));
+        assert!(q("tag = waf").program(&program));
+        assert!(q("not tag = waf").program(&program));
+        assert!(!q("not tag = waf and asset ~ cdn").program(&program));
+        assert!(!q("program = google and sc = 404").program(&program));
+
+        assert_eq!(
+            assets(r#"asset ~ "google" and not tag = "waf" and sc in [200, 302]"#),
+            vec!["https://api.google.com/v1"]
+        );
+        assert_eq!(
+            assets("asset !~ api and asset ~ cdn"),
+            vec!["cdn.google.com"]
+        );
+        assert_eq!(assets("sc >= 300 or tag = waf").len(), 2);
+        assert_eq!(assets("program = yahoo or sc = 302").len(), 1);
+        assert_eq!(assets("program = google or sc = 302").len(), 4);
+        assert!(assets("program = yahoo").is_empty());
+
+        // Asset keys only match assets of their type
+        assert_eq!(assets("sub ~ google").len(), 2);
+        assert!(assets("sub ~ google").iter().all(|a| !a.contains('/')));
+        assert_eq!(assets("url ~ google"), vec!["https://api.google.com/v1"]);
+        assert!(assets("cidr ~ google").is_empty());
+        assert!(!q("program_url ~ google").program(&program));
+    }
+}
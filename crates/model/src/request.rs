@@ -21,70 +21,129 @@ impl FromStr for Request {
     }
 }
 
-impl PartialEq for Request {
-    fn eq(&self, other: &Self) -> bool {
-        other.url[..url::Position::BeforePath] == self.url[..url::Position::BeforePath]
-            && match (self.url.path_segments(), other.url.path_segments()) {
-                (None, None) => true,
-                (None, Some(_)) => false,
-                (Some(_), None) => false,
-                (Some(s_path), Some(o_path)) => {
-                    let s_path: Vec<_> = s_path
-                        .filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                        .collect();
-                    let o_path: Vec<_> = o_path
-                        .filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
-                        .collect();
+/// How two urls of a program are considered the same endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Same path segments and same query keys
+    Strict,
+    /// Numeric segments are ignored and one differing segment is tolerated
+    #[default]
+    Heuristic,
+    /// Uuid, hex, date, number and locale segments are replaced with placeholders
+    Pattern,
+}
 
-                    if s_path.len() != o_path.len() {
-                        false
-                    } else {
-                        let a: HashSet<_> = other
-                            .url
-                            .query_pairs()
-                            .map(|(k, _)| k)
-                            .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
-                            .collect();
-                        let b: HashSet<_> = self
-                            .url
-                            .query_pairs()
-                            .map(|(k, _)| k)
-                            .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
-                            .collect();
-
-                        let diff = if !(a.is_empty() ^ b.is_empty())
-                            && (a.is_subset(&b) || b.is_subset(&a))
-                        {
-                            2
-                        } else {
-                            1
-                        };
+impl Normalization {
+    pub fn eq(&self, a: &Request, b: &Request) -> bool {
+        match self {
+            Normalization::Heuristic => heuristic_eq(a, b),
+            _ => self.template(a) == self.template(b),
+        }
+    }
+
+    /// Index keys of a url, equal urls always share one of them.
+    /// Heuristic urls have a key per path segment with that segment masked,
+    /// as they are equal if all but one segment are the same.
+    pub fn keys(&self, req: &Request) -> Vec<String> {
+        match self {
+            Normalization::Heuristic => {
+                let origin = &req.url[..url::Position::BeforePath];
+                let path = heuristic_path(req);
+                if path.is_empty() {
+                    return vec![format!("{origin}/")];
+                }
+                (0..path.len())
+                    .map(|i| {
+                        let mut masked = path.clone();
+                        masked[i] = "*";
+                        format!("{}/{}", origin, masked.join("/"))
+                    })
+                    .collect()
+            }
+            _ => vec![self.template(req)],
+        }
+    }
+
+    /// Canonical form of a url for the policy: origin, path template and sorted query keys
+    pub fn template(&self, req: &Request) -> String {
+        let mut path: Vec<String> = req.url.path_segments().map_or(vec![], |s| {
+            s.filter(|p| !p.is_empty()).map(String::from).collect()
+        });
 
-                        s_path
-                            .iter()
-                            .zip(o_path.iter())
-                            .filter(|(s, o)| s != o)
-                            .count()
-                            < diff
+        match self {
+            Normalization::Strict => (),
+            Normalization::Heuristic => path.retain(|p| p.parse::<usize>().is_err()),
+            Normalization::Pattern => {
+                for (i, p) in path.iter_mut().enumerate() {
+                    if let Some(placeholder) = placeholder(p, i == 0) {
+                        *p = placeholder.to_string();
                     }
                 }
             }
+        }
+
+        let mut keys: Vec<_> = req
+            .url
+            .query_pairs()
+            .map(|(k, _)| k.to_string())
+            .filter(|k| !k.is_empty())
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        format!(
+            "{}/{}?{}",
+            &req.url[..url::Position::BeforePath],
+            path.join("/"),
+            keys.join("&")
+        )
     }
 }
 
-impl Eq for Request {}
+fn placeholder(segment: &str, first: bool) -> Option<&'static str> {
+    let is_hex = |s: &str| s.chars().all(|c| c.is_ascii_hexdigit());
+    let parts: Vec<_> = segment.split('-').collect();
 
-impl Ord for Request {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.url[..url::Position::BeforePath] != other.url[..url::Position::BeforePath] {
-            return self.url[..url::Position::BeforePath]
-                .cmp(&other.url[..url::Position::BeforePath]);
-        }
+    if segment.parse::<usize>().is_ok() {
+        Some("{int}")
+    } else if parts.iter().map(|p| p.len()).eq([8, 4, 4, 4, 12]) && parts.iter().all(|p| is_hex(p))
+    {
+        Some("{uuid}")
+    } else if segment.len() >= 8 && is_hex(segment) && segment.chars().any(|c| c.is_ascii_digit()) {
+        Some("{hex}")
+    } else if chrono::NaiveDate::parse_from_str(segment, "%Y-%m-%d").is_ok()
+        || chrono::NaiveDate::parse_from_str(segment, "%Y_%m_%d").is_ok()
+    {
+        Some("{date}")
+    } else if first
+        && segment.len() <= 5
+        && segment
+            .split(['-', '_'])
+            .enumerate()
+            .all(|(i, p)| p.len() == 2 && i < 2 && p.chars().all(|c| c.is_ascii_alphabetic()))
+        && segment[..2].chars().all(|c| c.is_ascii_lowercase())
+    {
+        Some("{locale}")
+    } else {
+        None
+    }
+}
+
+// Non numeric path segments
+fn heuristic_path(req: &Request) -> Vec<&str> {
+    req.url.path_segments().map_or(vec![], |s| {
+        s.filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
+            .collect()
+    })
+}
 
-        match (self.url.path_segments(), other.url.path_segments()) {
-            (None, None) => (),
-            (None, Some(_)) => return Ordering::Less,
-            (Some(_), None) => return Ordering::Greater,
+fn heuristic_eq(s: &Request, o: &Request) -> bool {
+    o.url[..url::Position::BeforePath] == s.url[..url::Position::BeforePath]
+        && match (s.url.path_segments(), o.url.path_segments()) {
+            (None, None) => true,
+            (None, Some(_)) => false,
+            (Some(_), None) => false,
             (Some(s_path), Some(o_path)) => {
                 let s_path: Vec<_> = s_path
                     .filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
@@ -93,45 +152,75 @@ impl Ord for Request {
                     .filter(|p| !p.is_empty() && p.parse::<usize>().is_err())
                     .collect();
 
-                match s_path.len().cmp(&o_path.len()) {
-                    Ordering::Less => return Ordering::Less,
-                    Ordering::Greater => return Ordering::Greater,
-                    Ordering::Equal => {
-                        let a: HashSet<_> = other
-                            .url
-                            .query_pairs()
-                            .map(|(k, _)| k)
-                            .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
-                            .collect();
-                        let b: HashSet<_> = self
-                            .url
-                            .query_pairs()
-                            .map(|(k, _)| k)
-                            .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
-                            .collect();
-
-                        let diff = if !(a.is_empty() ^ b.is_empty())
-                            && (a.is_subset(&b) || b.is_subset(&a))
-                        {
-                            1
+                if s_path.len() != o_path.len() {
+                    false
+                } else {
+                    let a: HashSet<_> = o
+                        .url
+                        .query_pairs()
+                        .map(|(k, _)| k)
+                        .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
+                        .collect();
+                    let b: HashSet<_> = s
+                        .url
+                        .query_pairs()
+                        .map(|(k, _)| k)
+                        .filter(|s| !s.is_empty() && s.parse::<usize>().is_err())
+                        .collect();
+
+                    let diff =
+                        if !(a.is_empty() ^ b.is_empty()) && (a.is_subset(&b) || b.is_subset(&a)) {
+                            2
                         } else {
-                            0
+                            1
                         };
 
-                        let diffs: Vec<_> = s_path
-                            .iter()
-                            .zip(o_path.iter())
-                            .filter(|(s, o)| s != o)
-                            .collect();
-                        if diffs.len() > diff {
-                            return diffs[0].0.cmp(diffs[0].1);
-                        }
-                    }
+                    s_path
+                        .iter()
+                        .zip(o_path.iter())
+                        .filter(|(s, o)| s != o)
+                        .count()
+                        < diff
                 }
             }
         }
+}
 
-        Ordering::Equal
+// Equality and order are strict, fuzzy matching belongs to `Normalization`
+impl PartialEq for Request {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Request {}
+
+// Origin, path segments and then the sorted query keys, like the strict template
+impl Ord for Request {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn segments(r: &Request) -> impl Iterator<Item = &str> {
+            r.url
+                .path_segments()
+                .into_iter()
+                .flatten()
+                .filter(|p| !p.is_empty())
+        }
+        fn keys(r: &Request) -> Vec<std::borrow::Cow<'_, str>> {
+            let mut keys: Vec<_> = r
+                .url
+                .query_pairs()
+                .map(|(k, _)| k)
+                .filter(|k| !k.is_empty())
+                .collect();
+            keys.sort();
+            keys.dedup();
+            keys
+        }
+
+        self.url[..url::Position::BeforePath]
+            .cmp(&other.url[..url::Position::BeforePath])
+            .then_with(|| segments(self).cmp(segments(other)))
+            .then_with(|| keys(self).cmp(&keys(other)))
     }
 }
 
@@ -164,50 +253,125 @@ mod test {
 
     #[test]
     fn test_eq() {
-        use super::super::AssetName;
-        use std::str::FromStr;
+        use super::*;
+
+        let eq = |a: &str, b: &str| {
+            Normalization::Heuristic.eq(
+                &Request::from_str(a).unwrap(),
+                &Request::from_str(b).unwrap(),
+            )
+        };
+
+        assert!(!eq(
+            "https://test.com/voucher/settings/template/popover/voucher/settings/text/text/lib/css/libs.css?v=c644c27a4",
+            "https://test.com/voucher/settings/template/popover/voucher/settings/voucher/text/settings/voucher/settings/lib/css/libs.css?v=c644c27a4"
+        ));
 
         assert_ne!(
-            AssetName::from_str("https://test.com/voucher/settings/template/popover/voucher/settings/text/text/lib/css/libs.css?v=c644c27a4").unwrap(),
-            AssetName::from_str("https://test.com/voucher/settings/template/popover/voucher/settings/voucher/text/settings/voucher/settings/lib/css/libs.css?v=c644c27a4").unwrap()
+            Request::from_str("https://test.com/tag/a/feed/").unwrap(),
+            Request::from_str("https://test.com/tag/b/feed/").unwrap()
         );
+        assert!(eq(
+            "https://test.com/tag/a/feed/",
+            "https://test.com/tag/b/feed/"
+        ));
 
-        assert_eq!(
-            AssetName::from_str("https://test.com/tag/a/feed/").unwrap(),
-            AssetName::from_str("https://test.com/tag/b/feed/").unwrap()
-        );
+        assert!(eq(
+            "http://test.com/eagle_kp_webapp/login/login.action;jsessionid=2acc8603184240a22e4fa5fe3525",
+            "http://test.com/eagle_kp_webapp/login/login.action;jsessionid=2b0107dc883c28019e877799d07e"
+        ));
 
-        assert_eq!(
-            AssetName::from_str("http://test.com/eagle_kp_webapp/login/login.action;jsessionid=2acc8603184240a22e4fa5fe3525").unwrap(),
-            AssetName::from_str("http://test.com/eagle_kp_webapp/login/login.action;jsessionid=2b0107dc883c28019e877799d07e").unwrap()
-        );
+        assert!(eq(
+            "https://test.com/category/a/feed/",
+            "https://test.com/category/b/feed/"
+        ));
 
-        assert_eq!(
-            AssetName::from_str("https://test.com/category/a/feed/").unwrap(),
-            AssetName::from_str("https://test.com/category/b/feed/").unwrap()
-        );
+        assert!(!eq("https://a.com", "https://b.com"));
+        assert!(eq("https://a.com/", "https://a.com"));
 
-        assert_ne!(
-            AssetName::from_str("https://a.com").unwrap(),
-            AssetName::from_str("https://b.com").unwrap()
-        );
-        assert_eq!(
-            AssetName::from_str("https://a.com/").unwrap(),
-            AssetName::from_str("https://a.com").unwrap()
-        );
+        assert!(eq("https://a.com/a/b/c/4", "https://a.com/a/d/c"));
+        assert!(!eq("https://a.com/a/b/c/", "https://a.com/a/d/e"));
+
+        assert!(!eq("https://a.com/a/23/b", "https://b.com/b/a/23/a/"));
+    }
+
+    #[test]
+    fn normalization() {
+        use super::*;
+
+        let eq = |policy: Normalization, a: &str, b: &str| {
+            policy.eq(
+                &Request::from_str(a).unwrap(),
+                &Request::from_str(b).unwrap(),
+            )
+        };
+
+        assert!(eq(
+            Normalization::Heuristic,
+            "https://test.com/tag/a/feed/",
+            "https://test.com/tag/b/feed/"
+        ));
+        assert!(!eq(
+            Normalization::Strict,
+            "https://test.com/tag/a/feed/",
+            "https://test.com/tag/b/feed/"
+        ));
+        assert!(eq(
+            Normalization::Strict,
+            "https://test.com/tag/a/feed?b=1&a=2",
+            "https://test.com/tag/a/feed/?a=3&b=4"
+        ));
+        assert!(!eq(
+            Normalization::Pattern,
+            "https://test.com/tag/a/feed/",
+            "https://test.com/tag/b/feed/"
+        ));
+        assert!(eq(
+            Normalization::Pattern,
+            "https://test.com/en-us/user/8c4c0e4e-6c7b-4f5e-9a1d-0d3c5b7a9e21/avatar",
+            "https://test.com/de/user/1d2e3f4a-5b6c-4d7e-8f90-a1b2c3d4e5f6/avatar"
+        ));
+        assert!(eq(
+            Normalization::Pattern,
+            "https://test.com/blog/2022-01-31/5f4dcc3b5aa765d6",
+            "https://test.com/blog/2023-12-01/e10adc3949ba59ab"
+        ));
+        assert!(!eq(
+            Normalization::Pattern,
+            "https://test.com/blog/2022-01-31/",
+            "https://test.com/blog/latest/"
+        ));
+    }
+
+    #[test]
+    fn keys() {
+        use super::*;
+
+        let keys = |a: &str| Normalization::Heuristic.keys(&Request::from_str(a).unwrap());
 
         assert_eq!(
-            AssetName::from_str("https://a.com/a/b/c/4").unwrap(),
-            AssetName::from_str("https://a.com/a/d/c").unwrap()
-        );
-        assert_ne!(
-            AssetName::from_str("https://a.com/a/b/c/").unwrap(),
-            AssetName::from_str("https://a.com/a/d/e").unwrap()
+            keys("https://test.com/tag/a/7/feed?x=1"),
+            vec![
+                "https://test.com/*/a/feed",
+                "https://test.com/tag/*/feed",
+                "https://test.com/tag/a/*"
+            ]
         );
+        assert_eq!(keys("https://test.com"), vec!["https://test.com/"]);
+        assert!(keys("https://test.com/tag/b/feed/")
+            .iter()
+            .any(|k| keys("https://test.com/tag/a/feed").contains(k)));
 
-        assert_ne!(
-            AssetName::from_str("https://a.com/a/23/b").unwrap(),
-            AssetName::from_str("https://b.com/b/a/23/a/").unwrap()
+        let cmp = |a: &str, b: &str| {
+            Request::from_str(a)
+                .unwrap()
+                .cmp(&Request::from_str(b).unwrap())
+        };
+        assert_eq!(
+            cmp("http://a.com/x?b=1&a=2", "http://a.com/x/?a=3&b"),
+            Ordering::Equal
         );
+        assert_eq!(cmp("http://a.com/x", "http://a.com/x/y"), Ordering::Less);
+        assert_eq!(cmp("http://a.com/x?a", "http://a.com/x"), Ordering::Greater);
     }
 }
@@ -1,4 +1,6 @@
 use super::*;
+use regex::Regex;
+use std::collections::hash_map::Entry;
 
 fn parse(text: &str, regex: &Regex) -> Vec<Asset> {
     regex
@@ -8,7 +10,7 @@ fn parse(text: &str, regex: &Regex) -> Vec<Asset> {
 
             let tags = if let Some(name) = get("tag") {
                 let values = if let Some(value) = get("value") {
-                    value.split(',').map(|s| s.to_string()).collect()
+                    value.split(',').map(Value::from).collect()
                 } else {
                     vec![]
                 };
@@ -16,7 +18,7 @@ fn parse(text: &str, regex: &Regex) -> Vec<Asset> {
                 name.split(',')
                     .map(|name| Tag {
                         name: name.to_string(),
-                        severity: get("severity"),
+                        severity: get("severity").and_then(|s| Severity::from_str(&s).ok()),
                         values: values.clone(),
                         ..Default::default()
                     })
@@ -36,6 +38,9 @@ fn parse(text: &str, regex: &Regex) -> Vec<Asset> {
                         name,
                         tags,
                         start: Time(Utc::now()),
+                        update: None,
+                        dns: None,
+                        wildcard: None,
                     })
                 } else {
                     warn!("Invalid asset: {}", name);
@@ -49,6 +54,73 @@ fn parse(text: &str, regex: &Regex) -> Vec<Asset> {
         .collect()
 }
 
+// Bounded, so commands wait for the writer instead of piling up assets
+const BUFFER: usize = 4096;
+const BATCH: usize = 512;
+
+#[derive(Default)]
+pub struct Metrics {
+    pub lines: AtomicUsize,
+    pub assets: AtomicUsize,
+    pub inserted: AtomicUsize,
+    pub rejected: AtomicUsize,
+    pub batches: AtomicUsize,
+    pub waits: AtomicUsize,
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let get = |n: &AtomicUsize| n.load(atomic::Ordering::Relaxed);
+        write!(
+            f,
+            "Lines: {} Assets: {} Inserted: {} Rejected: {} Batches: {} Waits: {}",
+            get(&self.lines),
+            get(&self.assets),
+            get(&self.inserted),
+            get(&self.rejected),
+            get(&self.batches),
+            get(&self.waits),
+        )
+    }
+}
+
+// The only writer of luna while commands are running
+fn ingest(luna: &mut Luna, rx: mpsc::Receiver<Asset>, metrics: &Metrics) {
+    while let Ok(asset) = rx.recv() {
+        let mut batch = vec![asset];
+        batch.extend(rx.try_iter().take(BATCH - 1));
+        metrics.batches.fetch_add(1, atomic::Ordering::Relaxed);
+
+        // Merged within the batch first, so luna is searched once per name
+        let mut names: HashMap<String, usize> = HashMap::new();
+        let mut merged: Vec<(Asset, usize)> = vec![];
+        for asset in batch {
+            match names.entry(asset.name.to_string()) {
+                Entry::Occupied(i) => {
+                    let (a, count) = &mut merged[*i.get()];
+                    a.merge(asset);
+                    *count += 1;
+                }
+                Entry::Vacant(e) => {
+                    e.insert(merged.len());
+                    merged.push((asset, 1));
+                }
+            }
+        }
+
+        for (asset, count) in merged {
+            debug!("Insert: {}", asset.stringify(2));
+            match luna.insert_asset(asset, None) {
+                Ok(_) => metrics.inserted.fetch_add(count, atomic::Ordering::Relaxed),
+                Err(err) => {
+                    warn!("{err}");
+                    metrics.rejected.fetch_add(count, atomic::Ordering::Relaxed)
+                }
+            };
+        }
+    }
+}
+
 pub struct Script {
     pub verbose: u8,
     pub cd: String,
@@ -58,7 +130,7 @@ pub struct Script {
 }
 
 impl Script {
-    fn execute(&self, luna: &mut Luna, filter: &Filter, term: Arc<AtomicBool>) {
+    fn execute(&self, luna: &mut Luna, filter: &Filter, term: Arc<AtomicBool>) -> Metrics {
         debug!("{}", self.command);
 
         let elements = luna.find(self.field, filter, 0);
@@ -84,74 +156,102 @@ impl Script {
                 .with_finish(ProgressFinish::WithMessage(self.command.clone().into()));
         }
 
-        let luna = Mutex::new(luna);
+        let metrics = Metrics::default();
+        let (tx, rx) = mpsc::sync_channel(BUFFER);
 
-        elements.par_iter().for_each(|input| {
-            if term.load(atomic::Ordering::Relaxed) {
-                warn!("Command aborted! {} => {}", input, self.command);
-                return;
-            }
+        std::thread::scope(|s| {
+            let writer = s.spawn(|| ingest(luna, rx, &metrics));
 
-            let cmd = self.command.replace(&self.field.substitution(), input);
-            debug!("Command: {}", &cmd);
-
-            pb.set_message(cmd.clone());
-
-            let mut child = match Command::new("sh")
-                .current_dir(&self.cd)
-                .arg("-c")
-                .arg(&cmd)
-                .stdout(Stdio::piped())
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(err) => {
-                    error!("{err}");
+            elements.par_iter().for_each_with(tx, |tx, input| {
+                if term.load(atomic::Ordering::Relaxed) {
+                    warn!("Command aborted! {} => {}", input, self.command);
                     return;
                 }
-            };
 
-            match child.stdout.as_mut() {
-                Some(stdout) => {
-                    let stdout_reader = BufReader::new(stdout);
-                    let stdout_lines = stdout_reader.lines();
+                let cmd = self.command.replace(self.field.substitution(), input);
+                debug!("Command: {}", &cmd);
+
+                pb.set_message(cmd.clone());
+
+                let mut child = match Command::new("sh")
+                    .current_dir(&self.cd)
+                    .arg("-c")
+                    .arg(&cmd)
+                    .stdout(Stdio::piped())
+                    .spawn()
+                {
+                    Ok(child) => child,
+                    Err(err) => {
+                        error!("{err}");
+                        return;
+                    }
+                };
 
-                    for line in stdout_lines {
-                        if term.load(atomic::Ordering::Relaxed) {
-                            warn!("Command aborted while reading stdout!");
-                            return;
-                        }
-                        match line {
-                            Ok(line) => {
-                                let assets = parse(&line, &self.regex);
-
-                                debug!("Stdout assets len: {} {}", &assets.len(), cmd);
-
-                                for asset in assets {
-                                    debug!("Insert: {}", asset.stringify(2));
-                                    if let Err(err) = luna.lock().unwrap().insert_asset(asset, None)
-                                    {
-                                        warn!("{err}");
-                                    };
-                                }
+                match child.stdout.as_mut() {
+                    Some(stdout) => {
+                        let stdout_reader = BufReader::new(stdout);
+                        let stdout_lines = stdout_reader.lines();
+
+                        for line in stdout_lines {
+                            if term.load(atomic::Ordering::Relaxed) {
+                                warn!("Command aborted while reading stdout!");
+                                return;
                             }
-                            Err(err) => {
-                                warn!("Error while reading lines from stdout: {err} {cmd}")
+                            match line {
+                                Ok(line) => {
+                                    let assets = parse(&line, &self.regex);
+
+                                    debug!("Stdout assets len: {} {}", &assets.len(), cmd);
+                                    metrics.lines.fetch_add(1, atomic::Ordering::Relaxed);
+                                    metrics
+                                        .assets
+                                        .fetch_add(assets.len(), atomic::Ordering::Relaxed);
+
+                                    for asset in assets {
+                                        let sent = match tx.try_send(asset) {
+                                            Err(mpsc::TrySendError::Full(asset)) => {
+                                                metrics
+                                                    .waits
+                                                    .fetch_add(1, atomic::Ordering::Relaxed);
+                                                tx.send(asset).is_ok()
+                                            }
+                                            res => res.is_ok(),
+                                        };
+                                        if !sent {
+                                            error!("Writer is gone: {cmd}");
+                                            return;
+                                        }
+                                    }
+                                }
+                                Err(err) => {
+                                    warn!("Error while reading lines from stdout: {err} {cmd}")
+                                }
                             }
                         }
                     }
+                    None => debug!("There is no stdout: {cmd}"),
                 }
-                None => debug!("There is no stdout: {cmd}"),
-            }
 
-            match child.wait() {
-                Ok(ok) => {
-                    debug!("Success Command with StatusCode {ok}: {cmd}");
-                    pb.inc(1);
+                match child.wait() {
+                    Ok(ok) => {
+                        debug!("Success Command with StatusCode {ok}: {cmd}");
+                        pb.inc(1);
+                    }
+                    Err(err) => debug!("Error in Waiting for command: {cmd} {err}"),
                 }
-                Err(err) => debug!("Error in Waiting for command: {cmd} {err}"),
+            });
+
+            if writer.join().is_err() {
+                error!("Writer panicked: {}", self.command);
             }
         });
+
+        info!("{} => {}", self.command, metrics);
+        if self.verbose > 0 {
+            pb.println(metrics.to_string());
+        }
+
+        metrics
     }
 }
 
@@ -183,7 +283,7 @@ pub struct ScriptCli {
 }
 
 impl ScriptCli {
-    #[allow(clippy::blocks_in_if_conditions)]
+    #[allow(clippy::blocks_in_conditions)]
     pub fn parse(self) -> Result<Scripts, Errors> {
         let mut scripts = vec![];
         let mut regex = String::new();
@@ -198,9 +298,12 @@ impl ScriptCli {
                 regex = line
                     .split_once('=')
                     .map_or("".to_string(), |p| p.1.trim().to_string())
-            } else if line.trim().chars().next().map_or(false, |c| {
-                c.is_ascii_alphabetic() || '.' == c || '/' == c || '\\' == c
-            }) {
+            } else if line
+                .trim()
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || '.' == c || '/' == c || '\\' == c)
+            {
                 if regex.is_empty() {
                     return Err("Where the fuck is the first regex?".into());
                 }
@@ -270,3 +373,114 @@ fn is_valid(regex: &Regex) -> bool {
                 || !(regex.capture_names().flatten().any(|x| x == "severity")
                     || regex.capture_names().flatten().any(|x| x == "value"))))
 }
+
+mod test {
+
+    #[test]
+    fn execute() {
+        use super::*;
+
+        let mut luna = Luna::default();
+        luna.insert_asset(
+            Asset::from_str("google.com").unwrap(),
+            Some(Program::from_str("google").unwrap()),
+        )
+        .unwrap();
+
+        let script = Script {
+            verbose: 0,
+            cd: ".".to_string(),
+            regex: Regex::new("(?P<asset>.+)").unwrap(),
+            command: r"printf 'a.google.com\nb.google.com\nyahoo.com\n'".to_string(),
+            field: Field::None,
+        };
+
+        let metrics = script.execute(
+            &mut luna,
+            &Filter::default(),
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        let get = |n: &AtomicUsize| n.load(atomic::Ordering::Relaxed);
+        assert_eq!(get(&metrics.lines), 3);
+        assert_eq!(get(&metrics.inserted), 2);
+        assert_eq!(get(&metrics.rejected), 1);
+        assert_eq!(luna.find(Field::Sub, &Filter::default(), 0).len(), 2);
+    }
+
+    #[test]
+    fn wildcard() {
+        use super::*;
+
+        let mut luna = Luna::default();
+        luna.insert_asset(
+            Asset::from_str("google.com").unwrap(),
+            Some(Program::from_str("google").unwrap()),
+        )
+        .unwrap();
+        let domain = AssetName::from_str("google.com").unwrap();
+        let fingerprint = Dns {
+            a: vec![[9, 9, 9, 9].into()],
+            ..Default::default()
+        };
+        luna.insert_wildcard(&domain, fingerprint.clone());
+
+        let script = Script {
+            verbose: 0,
+            cd: ".".to_string(),
+            regex: Regex::new("(?P<asset>.+)").unwrap(),
+            command: r"printf 'x.google.com\n'".to_string(),
+            field: Field::None,
+        };
+        script.execute(
+            &mut luna,
+            &Filter::default(),
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        // Screened once it is resolved
+        let x = AssetName::from_str("x.google.com").unwrap();
+        let tags = |luna: &mut Luna| -> Vec<String> {
+            let asset = luna.asset_by_name(&x).unwrap();
+            asset.tags.iter().map(|t| t.name.clone()).collect()
+        };
+        assert_eq!(tags(&mut luna), vec![tag::UNVERIFIED]);
+
+        luna.insert_dns(x.clone(), fingerprint).unwrap();
+        assert!(tags(&mut luna).contains(&tag::WILDCARD.to_string()));
+    }
+
+    #[test]
+    fn ingest() {
+        use super::*;
+
+        let mut luna = Luna::default();
+        luna.insert_asset(
+            Asset::from_str("google.com").unwrap(),
+            Some(Program::from_str("google").unwrap()),
+        )
+        .unwrap();
+
+        let regex = Regex::new("(?P<asset>[^ ]+) (?P<tag>.+)").unwrap();
+        let (tx, rx) = mpsc::sync_channel(BUFFER);
+        for line in ["a.google.com one", "a.google.com two", "yahoo.com three"] {
+            for asset in parse(line, &regex) {
+                tx.send(asset).unwrap();
+            }
+        }
+        drop(tx);
+
+        let metrics = Metrics::default();
+        super::ingest(&mut luna, rx, &metrics);
+
+        let get = |n: &AtomicUsize| n.load(atomic::Ordering::Relaxed);
+        assert_eq!(get(&metrics.batches), 1);
+        assert_eq!(get(&metrics.inserted), 2);
+        assert_eq!(get(&metrics.rejected), 1);
+
+        let a = luna
+            .asset_by_name(&AssetName::from_str("a.google.com").unwrap())
+            .unwrap();
+        assert_eq!(a.tags.len(), 2);
+    }
+}
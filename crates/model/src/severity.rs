@@ -0,0 +1,118 @@
+use super::*;
+
+/// Known severities are ordered, custom ones are below info
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Custom(String),
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn rank(&self) -> u8 {
+        match self {
+            Severity::Custom(_) => 0,
+            Severity::Info => 1,
+            Severity::Low => 2,
+            Severity::Medium => 3,
+            Severity::High => 4,
+            Severity::Critical => 5,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Severity::Custom(s) => s,
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+// Order of grouping keys, "none" is the last
+pub(crate) fn key_order(key: &str) -> (bool, Option<Severity>) {
+    (key != "none", Severity::from_str(key).ok())
+}
+
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank()
+            .cmp(&other.rank())
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Errors;
+
+    // Case insensitive, e.g. High, CRIT, informational
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Ok(match s.to_lowercase().as_str() {
+            "" => return Err("Empty severity".into()),
+            "info" | "informational" => Severity::Info,
+            "low" => Severity::Low,
+            "medium" | "med" | "moderate" => Severity::Medium,
+            "high" => Severity::High,
+            "critical" | "crit" => Severity::Critical,
+            _ => Severity::Custom(s.to_string()),
+        })
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for Severity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Severity::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+mod test {
+    #[test]
+    fn severity() {
+        use super::*;
+
+        assert_eq!(Severity::from_str(" HIGH ").unwrap(), Severity::High);
+        assert_eq!(Severity::from_str("Crit").unwrap(), Severity::Critical);
+        assert_eq!(
+            Severity::from_str("P1").unwrap(),
+            Severity::Custom("P1".to_string())
+        );
+        assert!(Severity::from_str("").is_err());
+
+        assert!(Severity::Critical > Severity::High);
+        assert!(Severity::Info > Severity::Custom("zzz".to_string()));
+
+        let json = serde_json::to_string(&vec![Severity::Medium]).unwrap();
+        assert_eq!(json, r#"["medium"]"#);
+        let severities: Vec<Severity> = serde_json::from_str(r#"["Low","x"]"#).unwrap();
+        assert_eq!(
+            severities,
+            vec![Severity::Low, Severity::Custom("x".to_string())]
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use super::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum Breakdown {
+    Program,
+    Platform,
+    Sc,
+    Severity,
+    Day,
+}
+
+#[derive(Debug, Default, Parser)]
+pub struct StatOptions {
+    #[clap(long, arg_enum, ignore_case = true, help = "Group counts")]
+    pub by: Option<Breakdown>,
+    #[clap(long, help = "New and total counts of each discovery day")]
+    pub growth: bool,
+    #[clap(long, default_value = "0", help = "Top hosts by url count")]
+    pub top: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Count {
+    pub programs: usize,
+    pub assets: usize,
+    pub domains: usize,
+    pub cidrs: usize,
+    pub subs: usize,
+    pub urls: usize,
+    pub tags: usize,
+}
+
+impl Count {
+    fn asset(&mut self, asset: &Asset) {
+        self.assets += 1;
+        match asset.name {
+            AssetName::Domain(_) => self.domains += 1,
+            AssetName::Cidr(_) => self.cidrs += 1,
+            AssetName::Subdomain(_) => self.subs += 1,
+            AssetName::Url(_) => self.urls += 1,
+        }
+    }
+
+    fn add(&mut self, other: &Count) {
+        self.programs += other.programs;
+        self.assets += other.assets;
+        self.domains += other.domains;
+        self.cidrs += other.cidrs;
+        self.subs += other.subs;
+        self.urls += other.urls;
+        self.tags += other.tags;
+    }
+
+    fn fields(&self) -> [(&'static str, usize); 7] {
+        [
+            ("programs", self.programs),
+            ("assets", self.assets),
+            ("domains", self.domains),
+            ("cidrs", self.cidrs),
+            ("subs", self.subs),
+            ("urls", self.urls),
+            ("tags", self.tags),
+        ]
+    }
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = self
+            .fields()
+            .iter()
+            .map(|(n, c)| format!("{n}: {c}"))
+            .collect();
+        write!(f, "{}", fields.join("  "))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+    pub key: String,
+    #[serde(flatten)]
+    pub count: Count,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Growth {
+    pub day: String,
+    pub new: Count,
+    pub total: Count,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeverityCount {
+    pub severity: Severity,
+    pub tags: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Host {
+    pub host: String,
+    pub urls: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub name: String,
+    pub version: String,
+    pub status: String,
+    #[serde(flatten)]
+    pub total: Count,
+    pub start: Time,
+    // The most severe first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub severities: Vec<SeverityCount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<Bucket>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub growth: Vec<Growth>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top: Vec<Host>,
+}
+
+fn day(time: Time) -> String {
+    time.0.with_timezone(&Local).format("%Y-%m-%d").to_string()
+}
+
+// Counts of buckets in the order they are seen, programs are counted once per bucket
+#[derive(Default)]
+struct Buckets {
+    positions: HashMap<String, usize>,
+    buckets: Vec<(Bucket, Option<usize>)>,
+}
+
+impl Buckets {
+    fn get(&mut self, key: String, program: usize) -> &mut Count {
+        let i = *self.positions.entry(key.clone()).or_insert_with(|| {
+            self.buckets.push((
+                Bucket {
+                    key,
+                    count: Count::default(),
+                },
+                None,
+            ));
+            self.buckets.len() - 1
+        });
+
+        let (bucket, last) = &mut self.buckets[i];
+        if *last != Some(program) {
+            *last = Some(program);
+            bucket.count.programs += 1;
+        }
+        &mut bucket.count
+    }
+}
+
+impl Luna {
+    pub fn count(&self) -> Count {
+        let mut count = Count {
+            programs: self.programs.len(),
+            ..Default::default()
+        };
+        for asset in self.programs.iter().flat_map(|p| &p.assets) {
+            count.asset(asset);
+            count.tags += asset.tags.len();
+        }
+        count
+    }
+
+    // Everything in a single pass over assets
+    pub fn stats(&self, opt: &StatOptions) -> Stats {
+        let mut total = Count::default();
+        let mut buckets = Buckets::default();
+        let mut days: BTreeMap<String, Count> = BTreeMap::new();
+        let mut hosts: HashMap<String, usize> = HashMap::new();
+        let mut severities: HashMap<&Severity, usize> = HashMap::new();
+        let none = || "none".to_string();
+
+        for (i, p) in self.programs.iter().enumerate() {
+            total.programs += 1;
+            if opt.growth {
+                days.entry(day(p.start)).or_default().programs += 1;
+            }
+            let key = match opt.by {
+                Some(Breakdown::Program) => Some(p.name.clone()),
+                Some(Breakdown::Platform) => Some(p.platform.clone().unwrap_or_else(none)),
+                _ => None,
+            };
+            if let Some(key) = &key {
+                buckets.get(key.clone(), i);
+            }
+
+            for a in &p.assets {
+                total.asset(a);
+                total.tags += a.tags.len();
+                for severity in a.tags.iter().filter_map(|t| t.severity.as_ref()) {
+                    *severities.entry(severity).or_default() += 1;
+                }
+
+                let keys = match (opt.by, &a.name) {
+                    (Some(Breakdown::Program | Breakdown::Platform), _) => {
+                        key.clone().into_iter().collect()
+                    }
+                    (Some(Breakdown::Sc), AssetName::Url(req)) => {
+                        vec![req.sc.clone().unwrap_or_else(none)]
+                    }
+                    (Some(Breakdown::Day), _) => vec![day(a.start)],
+                    (Some(Breakdown::Severity), _) => {
+                        let mut keys: Vec<String> = a
+                            .tags
+                            .iter()
+                            .map(|t| {
+                                t.severity
+                                    .as_ref()
+                                    .map_or_else(none, |s| s.as_str().to_lowercase())
+                            })
+                            .collect();
+                        keys.sort();
+                        keys.dedup();
+                        keys
+                    }
+                    _ => vec![],
+                };
+                for key in keys {
+                    let tags = match opt.by {
+                        Some(Breakdown::Severity) => a
+                            .tags
+                            .iter()
+                            .filter(|t| {
+                                t.severity
+                                    .as_ref()
+                                    .map_or_else(none, |s| s.as_str().to_lowercase())
+                                    == key
+                            })
+                            .count(),
+                        _ => a.tags.len(),
+                    };
+                    let count = buckets.get(key, i);
+                    count.asset(a);
+                    count.tags += tags;
+                }
+
+                if opt.growth {
+                    days.entry(day(a.start)).or_default().asset(a);
+                    for t in &a.tags {
+                        days.entry(day(t.start)).or_default().tags += 1;
+                    }
+                }
+
+                if opt.top > 0 {
+                    if let AssetName::Url(req) = &a.name {
+                        if let Some(host) = req.url.host_str() {
+                            *hosts.entry(host.to_string()).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut buckets: Vec<Bucket> = buckets.buckets.into_iter().map(|(b, _)| b).collect();
+        match opt.by {
+            Some(Breakdown::Severity) => {
+                buckets.sort_by_key(|b| std::cmp::Reverse(severity::key_order(&b.key)))
+            }
+            Some(Breakdown::Day) => buckets.sort_by(|a, b| a.key.cmp(&b.key)),
+            Some(Breakdown::Sc) => buckets.sort_by_key(|b| std::cmp::Reverse(b.count.urls)),
+            _ => {}
+        }
+
+        let mut sum = Count::default();
+        let growth = days
+            .into_iter()
+            .map(|(day, new)| {
+                sum.add(&new);
+                Growth {
+                    day,
+                    new,
+                    total: sum.clone(),
+                }
+            })
+            .collect();
+
+        let mut top: Vec<Host> = hosts
+            .into_iter()
+            .map(|(host, urls)| Host { host, urls })
+            .collect();
+        top.sort_by(|a, b| b.urls.cmp(&a.urls).then_with(|| a.host.cmp(&b.host)));
+        top.truncate(opt.top);
+
+        let mut severities: Vec<SeverityCount> = severities
+            .into_iter()
+            .map(|(severity, tags)| SeverityCount {
+                severity: severity.clone(),
+                tags,
+            })
+            .collect();
+        severities.sort_by(|a, b| b.severity.cmp(&a.severity));
+
+        Stats {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            status: self.status.clone(),
+            total,
+            start: self.start,
+            severities,
+            buckets,
+            growth,
+            top,
+        }
+    }
+}
+
+impl Stats {
+    pub fn render(&self, format: Format) -> Vec<String> {
+        let table = |names: Vec<String>, rows: Vec<Vec<String>>| Table {
+            lists: vec![false; names.len()],
+            names,
+            rows: rows
+                .into_iter()
+                .map(|r| r.into_iter().map(|v| vec![v]).collect())
+                .collect(),
+        };
+        let counts = |count: &Count| count.fields().map(|(_, c)| c.to_string()).to_vec();
+        let names = |first: &[&str]| {
+            first
+                .iter()
+                .copied()
+                .chain(Count::default().fields().map(|(n, _)| n))
+                .map(String::from)
+                .collect::<Vec<String>>()
+        };
+
+        match format {
+            Format::Json | Format::Jsonl => vec![serde_json::json!(self).to_string()],
+            Format::Text => {
+                let mut lines = vec![self.total.to_string()];
+                let mut section = |title: &str, rows: Vec<String>| {
+                    if !rows.is_empty() {
+                        lines.push(format!("{title}:"));
+                        lines.extend(rows.into_iter().map(|r| format!("    {r}")));
+                    }
+                };
+                section(
+                    "Severities",
+                    self.severities
+                        .iter()
+                        .map(|s| format!("{}  {}", s.severity, s.tags))
+                        .collect(),
+                );
+                section(
+                    "Buckets",
+                    self.buckets
+                        .iter()
+                        .map(|b| format!("{}  {}", b.key, b.count))
+                        .collect(),
+                );
+                section(
+                    "Growth",
+                    self.growth
+                        .iter()
+                        .map(|g| {
+                            format!(
+                                "{}  +{}  +{}  ({} assets)",
+                                g.day, g.new.assets, g.new.tags, g.total.assets
+                            )
+                        })
+                        .collect(),
+                );
+                section(
+                    "Top",
+                    self.top
+                        .iter()
+                        .map(|h| format!("{}  {}", h.host, h.urls))
+                        .collect(),
+                );
+                lines
+            }
+            // One table, the most specific one
+            _ if !self.top.is_empty() => format.render(&table(
+                vec!["host".to_string(), "urls".to_string()],
+                self.top
+                    .iter()
+                    .map(|h| vec![h.host.clone(), h.urls.to_string()])
+                    .collect(),
+            )),
+            _ if !self.growth.is_empty() => format.render(&table(
+                names(&["day"]),
+                self.growth
+                    .iter()
+                    .map(|g| {
+                        std::iter::once(g.day.clone())
+                            .chain(counts(&g.total))
+                            .collect()
+                    })
+                    .collect(),
+            )),
+            _ if !self.buckets.is_empty() => format.render(&table(
+                names(&["key"]),
+                self.buckets
+                    .iter()
+                    .map(|b| {
+                        std::iter::once(b.key.clone())
+                            .chain(counts(&b.count))
+                            .collect()
+                    })
+                    .collect(),
+            )),
+            _ => format.render(&table(
+                ["name", "version", "status"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(names(&[]))
+                    .chain(["start".to_string()])
+                    .collect(),
+                vec![
+                    [self.name.clone(), self.version.clone(), self.status.clone()]
+                        .into_iter()
+                        .chain(counts(&self.total))
+                        .chain([self.start.0.to_rfc3339()])
+                        .collect(),
+                ],
+            )),
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn stats() {
+        use super::*;
+
+        let mut luna = Luna::default();
+        for (asset, program, platform) in [
+            ("google.com", "google", Some("h1")),
+            ("yahoo.com", "yahoo", Some("h1")),
+            ("tesla.com", "tesla", None),
+        ] {
+            luna.insert_program(Program {
+                name: program.to_string(),
+                platform: platform.map(String::from),
+                assets: vec![Asset::from_str(asset).unwrap()],
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        for (url, sc) in [
+            ("https://api.google.com/a", "200"),
+            ("https://api.google.com/b/c", "200"),
+            ("https://www.google.com/", "302"),
+            ("https://www.yahoo.com/", "200"),
+        ] {
+            let mut asset = Asset::from_str(url).unwrap();
+            if let AssetName::Url(req) = &mut asset.name {
+                req.sc = Some(sc.to_string());
+            }
+            luna.insert_asset(asset, None).unwrap();
+        }
+        let mut tag = Tag::from_str("xss").unwrap();
+        tag.severity = Some(Severity::High);
+        luna.insert_tag(tag, &AssetName::from_str("https://www.yahoo.com/").unwrap())
+            .unwrap();
+
+        let stats = |by, growth, top| luna.stats(&StatOptions { by, growth, top });
+
+        let s = stats(None, false, 0);
+        assert_eq!(s.total.programs, 3);
+        assert_eq!(s.total.urls, 4);
+        assert_eq!(s.total.subs, 3);
+        assert_eq!(s.total.tags, 1);
+        assert_eq!(luna.count().assets, s.total.assets);
+        assert!(s.buckets.is_empty() && s.growth.is_empty() && s.top.is_empty());
+        assert_eq!(s.severities.len(), 1);
+        assert_eq!(s.render(Format::Text)[1..], ["Severities:", "    high  1"]);
+
+        let s = stats(Some(Breakdown::Platform), false, 0);
+        let keys: Vec<_> = s
+            .buckets
+            .iter()
+            .map(|b| (b.key.as_str(), b.count.programs, b.count.urls))
+            .collect();
+        assert_eq!(keys, vec![("h1", 2, 4), ("none", 1, 0)]);
+
+        let s = stats(Some(Breakdown::Sc), false, 0);
+        let keys: Vec<_> = s
+            .buckets
+            .iter()
+            .map(|b| (b.key.as_str(), b.count.urls))
+            .collect();
+        assert_eq!(keys, vec![("200", 3), ("302", 1)]);
+        assert_eq!(s.buckets[0].count.programs, 2);
+
+        let s = stats(Some(Breakdown::Severity), false, 0);
+        assert_eq!(s.buckets.len(), 1);
+        assert_eq!(
+            (s.buckets[0].key.as_str(), s.buckets[0].count.tags),
+            ("high", 1)
+        );
+
+        let s = stats(None, true, 2);
+        assert_eq!(s.growth.len(), 1);
+        assert_eq!(s.growth[0].total.assets, s.total.assets);
+        let top: Vec<_> = s.top.iter().map(|h| (h.host.as_str(), h.urls)).collect();
+        assert_eq!(top, vec![("api.google.com", 2), ("www.google.com", 1)]);
+        assert_eq!(
+            s.render(Format::Csv),
+            vec!["host,urls", "api.google.com,2", "www.google.com,1"]
+        );
+        assert!(s.render(Format::Json)[0].contains(r#""top":[{"host":"api.google.com","urls":2}"#));
+    }
+}
@@ -1,16 +1,243 @@
 use super::*;
 
+// Tag of assets that are generated or guessed and not seen yet
+pub const UNVERIFIED: &str = "unverified";
+// Tag of subdomains answered by the wildcard of their domain
+pub const WILDCARD: &str = "wildcard";
+// Tag of assets that other programs have too, with their names as values
+pub const SHARED: &str = "shared";
+
+/// Triage of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ArgEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    #[default]
+    New,
+    Triaged,
+    FalsePositive,
+    Reported,
+    Duplicate,
+    Fixed,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::New => "new",
+            Status::Triaged => "triaged",
+            Status::FalsePositive => "false-positive",
+            Status::Reported => "reported",
+            Status::Duplicate => "duplicate",
+            Status::Fixed => "fixed",
+        }
+    }
+}
+
+/// Changes to the triage of tags, left as they are if not given
+#[derive(Debug, Default, Parser)]
+pub struct Triage {
+    #[clap(long = "set-status", name = "SET_STATUS", arg_enum, ignore_case = true)]
+    pub status: Option<Status>,
+    #[clap(
+        long = "set-assignee",
+        name = "SET_ASSIGNEE",
+        help = "Empty to unassign"
+    )]
+    pub assignee: Option<String>,
+    #[clap(
+        long = "set-report",
+        name = "SET_REPORT",
+        help = "Url of the report, empty to remove"
+    )]
+    pub report: Option<String>,
+    #[clap(long = "add-note", name = "NOTE")]
+    pub note: Option<String>,
+}
+
+impl Triage {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none()
+            && self.assignee.is_none()
+            && self.report.is_none()
+            && self.note.is_none()
+    }
+}
+
+/// A value of a tag and how many times it was found
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "Stored", into = "Stored")]
+pub struct Value {
+    pub value: String,
+    pub count: usize,
+    // First and last seen, unknown for values of old files and of sets
+    pub start: Option<Time>,
+    pub update: Option<Time>,
+}
+
+// Values without counts are stored as plain strings, like older files
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum Stored {
+    Plain(String),
+    Counted {
+        value: String,
+        #[serde(default = "one")]
+        count: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<Time>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        update: Option<Time>,
+    },
+}
+
+fn one() -> usize {
+    1
+}
+
+impl From<Stored> for Value {
+    fn from(stored: Stored) -> Self {
+        match stored {
+            Stored::Plain(value) => Value::from(value),
+            Stored::Counted {
+                value,
+                count,
+                start,
+                update,
+            } => Value {
+                value,
+                count,
+                start,
+                update,
+            },
+        }
+    }
+}
+
+impl From<Value> for Stored {
+    fn from(v: Value) -> Self {
+        if v.count == 1 && v.start.is_none() && v.update.is_none() {
+            Stored::Plain(v.value)
+        } else {
+            Stored::Counted {
+                value: v.value,
+                count: v.count,
+                start: v.start,
+                update: v.update,
+            }
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value {
+            value,
+            count: 1,
+            start: None,
+            update: None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::from(value.to_string())
+    }
+}
+
+impl FromStr for Value {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Value::from(s))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Value {
+    // Last seen
+    pub fn seen(&self) -> Option<Time> {
+        self.update.or(self.start)
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        let seen = self.seen().max(other.seen());
+
+        self.count += other.count;
+        self.start = match (self.start, other.start) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.update = seen.filter(|s| self.start.is_some_and(|start| *s > start));
+    }
+
+    pub fn stringify(&self, v: u8) -> String {
+        let time = |t: Time| {
+            t.0.with_timezone(&Local::now().timezone())
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        };
+        match (v, self.start) {
+            (0, _) => self.value.clone(),
+            (1, _) | (_, None) => format!("{} ({})", self.value, self.count),
+            (_, Some(start)) => format!(
+                "{} ({}) [{} - {}]",
+                self.value,
+                self.count,
+                time(start),
+                time(self.seen().unwrap_or(start))
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Parser, Default, Deserialize, Serialize)]
 pub struct Tag {
     pub name: String,
     #[clap(long = "sv")]
-    pub severity: Option<String>,
+    pub severity: Option<Severity>,
 
     #[clap(long, multiple_values = true)]
-    pub values: Vec<String>,
+    pub values: Vec<Value>,
+    #[clap(long, help = "Keep at most this many values, the most found ones")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cap: Option<usize>,
+    #[clap(long, help = "Keep values as a set, without counts and times")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub set: bool,
+    // Values left out because of the cap
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped: usize,
+    // Positions of values, to not scan them all on every merge
+    #[clap(skip)]
+    #[serde(skip)]
+    pub(crate) index: HashMap<String, usize>,
 
     #[clap(skip)]
     pub start: Time,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<Time>,
+
+    // Triage, kept when scanners find the tag again
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+    #[clap(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
 }
 
 impl FromStr for Tag {
@@ -21,52 +248,198 @@ impl FromStr for Tag {
             name: s.to_string(),
             severity: None,
             values: vec![],
+            cap: None,
+            set: false,
+            dropped: 0,
+            index: HashMap::new(),
             start: Time::default(),
+            update: None,
+            status: None,
+            assignee: None,
+            report: None,
+            notes: vec![],
         })
     }
 }
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
 impl Tag {
+    // Last seen
+    pub fn seen(&self) -> Time {
+        self.update.unwrap_or(self.start)
+    }
+
     pub fn merge(&mut self, other: Self) {
         let new = self.start < other.start;
+        let seen = self.seen().max(other.seen());
 
         merge(&mut self.severity, other.severity, new);
+        merge(&mut self.status, other.status, new);
+        merge(&mut self.assignee, other.assignee, new);
+        merge(&mut self.report, other.report, new);
+        merge(&mut self.cap, other.cap, new);
+        self.set |= other.set;
+        self.dropped += other.dropped;
 
         self.start = self.start.min(other.start);
+        self.update = (seen > self.start).then_some(seen);
+
+        let (start, update) = (self.start, self.update);
+        for value in self.values.iter_mut().filter(|v| v.start.is_none()) {
+            value.start = Some(start);
+            value.update = update;
+        }
+        for mut value in other.values {
+            if value.start.is_none() {
+                value.start = Some(other.start);
+                value.update = other.update;
+            }
+            self.insert_value(value);
+        }
+        if self.set {
+            self.values.iter_mut().for_each(|v| {
+                v.count = 1;
+                v.start = None;
+                v.update = None;
+            });
+        }
+        self.shrink();
+        for note in other.notes {
+            if !self.notes.contains(&note) {
+                self.notes.push(note);
+            }
+        }
+    }
+
+    fn position(&mut self, value: &str) -> Option<usize> {
+        let valid = |i: &usize, values: &[Value]| values.get(*i).is_some_and(|v| v.value == value);
+        match self.index.get(value) {
+            Some(i) if valid(i, &self.values) => Some(*i),
+            // Values were changed in place, e.g. removed
+            _ if self.index.len() != self.values.len() || self.index.contains_key(value) => {
+                self.reindex();
+                self.index.get(value).copied()
+            }
+            _ => None,
+        }
+    }
+
+    // Repeated values, e.g. of older files, are merged so the index stays valid
+    fn reindex(&mut self) {
+        self.index.clear();
+        for value in std::mem::take(&mut self.values) {
+            match self.index.get(&value.value) {
+                Some(&i) => self.values[i].merge(value),
+                None => {
+                    self.index.insert(value.value.clone(), self.values.len());
+                    self.values.push(value);
+                }
+            }
+        }
+    }
 
-        for value in other.values {
-            if !self.values.iter_mut().any(|t| t == &value) {
+    // Count a found value, or drop it if the tag is full
+    pub fn insert_value(&mut self, value: Value) {
+        match self.position(&value.value) {
+            Some(i) => self.values[i].merge(value),
+            None if self.cap.is_some_and(|cap| self.values.len() >= cap) => {
+                self.dropped += value.count
+            }
+            None => {
+                self.index.insert(value.value.clone(), self.values.len());
                 self.values.push(value);
             }
         }
     }
 
+    // Keep the most found values when the cap is lowered
+    fn shrink(&mut self) {
+        if let Some(cap) = self.cap.filter(|cap| self.values.len() > *cap) {
+            self.values.sort_by_key(|v| std::cmp::Reverse(v.count));
+            self.dropped += self.values.drain(cap..).map(|v| v.count).sum::<usize>();
+            self.index.clear();
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.unwrap_or_default()
+    }
+
+    pub fn triage(&mut self, triage: &Triage) {
+        let text = |s: &String| (!s.is_empty()).then(|| s.clone());
+
+        if let Some(status) = triage.status {
+            self.status = Some(status);
+        }
+        if let Some(assignee) = &triage.assignee {
+            self.assignee = text(assignee);
+        }
+        if let Some(report) = &triage.report {
+            self.report = text(report);
+        }
+        if let Some(note) = &triage.note {
+            self.notes.push(note.clone());
+        }
+    }
+
     pub fn stringify(&self, v: u8) -> String {
         match v {
             0 => self.name.to_string(),
             1 => format!(
                 "{} [{}]",
                 self.name,
-                self.severity.as_ref().map_or("", |s| s),
+                self.severity.as_ref().map_or("", |s| s.as_str()),
             ),
             2 => format!(
                 "{} [{}] [{}]",
                 self.name,
-                self.severity.as_ref().map_or("", |s| s),
-                self.values.join(", ")
+                self.severity.as_ref().map_or("", |s| s.as_str()),
+                self.values
+                    .iter()
+                    .map(|v| v.value.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
             ),
             3 => format!(
                 "{} [{}]
-    Values: [{}{}
+    Status: {}{}{}
+    Values: [{}{}{}
+    Notes:  [{}{}
     Start:  {}
     ",
                 self.name,
-                self.severity.as_ref().map_or("", |s| s),
+                self.severity.as_ref().map_or("", |s| s.as_str()),
+                self.status().as_str(),
+                self.assignee
+                    .as_ref()
+                    .map_or("".to_string(), |a| format!(" @{}", a)),
+                self.report
+                    .as_ref()
+                    .map_or("".to_string(), |r| format!(" {}", r)),
                 self.values
+                    .iter()
+                    .map(|v| format!("\n        {}", v.stringify(2)))
+                    .collect::<Vec<String>>()
+                    .join(""),
+                if self.dropped > 0 {
+                    format!("\n        ... {} dropped", self.dropped)
+                } else {
+                    "".to_string()
+                },
+                if self.values.is_empty() && self.dropped == 0 {
+                    "]"
+                } else {
+                    "\n    ]"
+                },
+                self.notes
                     .iter()
                     .map(|s| format!("\n        {}", s))
                     .collect::<Vec<String>>()
                     .join(""),
-                if self.values.is_empty() {
+                if self.notes.is_empty() {
                     "]"
                 } else {
                     "\n    ]"
@@ -1,12 +1,74 @@
 use super::*;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub struct Time(pub DateTime<Utc>);
+
+// 90m 36h 7d 2w
+fn duration(s: &str) -> Option<chrono::Duration> {
+    let (i, unit) = s.char_indices().last()?;
+    let n = s[..i].parse::<i64>().ok()?;
+    match unit {
+        's' => Some(chrono::Duration::seconds(n)),
+        'm' => Some(chrono::Duration::minutes(n)),
+        'h' => Some(chrono::Duration::hours(n)),
+        'd' => Some(chrono::Duration::days(n)),
+        'w' => Some(chrono::Duration::weeks(n)),
+        _ => None,
+    }
+}
+
+// Local time if there is no zone
+fn datetime(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Utc));
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| date(s).and_then(|d| d.and_hms_opt(0, 0, 0)))?;
+    local(&naive)
+}
+
+fn date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+fn local(naive: &NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
 impl FromStr for Time {
     type Err = Errors;
 
+    // Hours ago, a duration ago or a date
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(Utc::now() - chrono::Duration::hours(s.parse()?)))
+        let s = s.trim();
+        if let Ok(hours) = s.parse() {
+            Ok(Self(Utc::now() - chrono::Duration::hours(hours)))
+        } else if let Some(d) = duration(s) {
+            Ok(Self(Utc::now() - d))
+        } else if let Some(t) = datetime(s) {
+            Ok(Self(t))
+        } else {
+            Err(format!("Invalid time: {s}").into())
+        }
+    }
+}
+
+impl Time {
+    // Exclusive end of a range, so a date alone includes that whole day
+    pub fn end(s: &str) -> Result<Self, Errors> {
+        match date(s.trim()).and_then(|d| d.succ_opt()?.and_hms_opt(0, 0, 0)) {
+            Some(naive) => local(&naive)
+                .map(Self)
+                .ok_or_else(|| format!("Invalid time: {s}").into()),
+            None => Self::from_str(s),
+        }
     }
 }
 
@@ -15,3 +77,43 @@ impl Default for Time {
         Self(Utc::now())
     }
 }
+
+mod test {
+
+    #[test]
+    fn from_str() {
+        use super::*;
+
+        let ago = |s: &str| {
+            let t = Time::from_str(s).unwrap();
+            Utc::now() - t.0
+        };
+        assert_eq!(ago("48").num_hours(), 48);
+        assert_eq!(ago("7d").num_days(), 7);
+        assert_eq!(ago("90m").num_minutes(), 90);
+        assert_eq!(ago("2w").num_weeks(), 2);
+
+        assert_eq!(
+            Time::from_str("2026-10-01T12:00:00Z")
+                .unwrap()
+                .0
+                .to_rfc3339(),
+            "2026-10-01T12:00:00+00:00"
+        );
+        assert!(
+            Time::from_str("2026-10-01").unwrap() < Time::from_str("2026-10-01 00:00:01").unwrap()
+        );
+        assert_eq!(
+            Time::end("2026-10-01").unwrap(),
+            Time::from_str("2026-10-02").unwrap()
+        );
+        assert_eq!(
+            Time::end("2026-10-01 12:00").unwrap(),
+            Time::from_str("2026-10-01 12:00").unwrap()
+        );
+        assert!(Time::from_str("7y").is_err());
+        assert!(Time::from_str("d").is_err());
+        assert!(Time::from_str("").is_err());
+        assert!(Time::end("").is_err());
+    }
+}
@@ -0,0 +1,580 @@
+use addr::parse_domain_name;
+use log::{debug, warn};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    net::UdpSocket,
+    task::JoinSet,
+    time::{sleep_until, timeout, Instant},
+};
+
+mod message;
+use message::{Answer, A, AAAA};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub name: String,
+    pub reason: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub resolvers: Vec<SocketAddr>,
+    /// Most queries per second, 0 is unlimited
+    pub rate: u32,
+    /// Names resolved at the same time
+    pub concurrency: usize,
+    pub timeout: Duration,
+    /// Tries with the next resolvers after a timeout or failure
+    pub retries: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolvers: vec![
+                SocketAddr::from(([1, 1, 1, 1], 53)),
+                SocketAddr::from(([8, 8, 8, 8], 53)),
+            ],
+            rate: 100,
+            concurrency: 50,
+            timeout: Duration::from_secs(2),
+            retries: 2,
+        }
+    }
+}
+
+impl Config {
+    /// One resolver per line as `ip`, `ip:port` or `nameserver ip` (resolv.conf)
+    pub fn parse_resolvers(s: &str) -> Result<Vec<SocketAddr>, String> {
+        let mut resolvers = vec![];
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let value = match line.strip_prefix("nameserver") {
+                Some(ip) => ip.trim(),
+                None if line.is_empty() || line.contains(char::is_whitespace) => continue,
+                None => line,
+            };
+
+            let addr = value
+                .parse::<SocketAddr>()
+                .or_else(|_| value.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+                .map_err(|_| format!("Invalid resolver: {}", value))?;
+            resolvers.push(addr);
+        }
+
+        Ok(resolvers)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let resolvers = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let resolvers = Self::parse_resolvers(&resolvers)?;
+        if resolvers.is_empty() {
+            return Err(format!("No resolvers in {}", path.display()));
+        }
+
+        Ok(Self {
+            resolvers,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Records {
+    pub a: Vec<Ipv4Addr>,
+    pub aaaa: Vec<Ipv6Addr>,
+    pub cname: Vec<String>,
+}
+
+impl Records {
+    pub fn is_empty(&self) -> bool {
+        self.a.is_empty() && self.aaaa.is_empty() && self.cname.is_empty()
+    }
+
+    fn extend(&mut self, answer: Answer) {
+        fn push<T: PartialEq>(all: &mut Vec<T>, new: Vec<T>) {
+            for item in new {
+                if !all.contains(&item) {
+                    all.push(item);
+                }
+            }
+        }
+        push(&mut self.a, answer.a);
+        push(&mut self.aaaa, answer.aaaa);
+        push(&mut self.cname, answer.cname);
+    }
+
+    // Wildcard answers are the same for every name
+    fn within(&self, wildcard: &Records) -> bool {
+        !self.is_empty()
+            && self.a.iter().all(|ip| wildcard.a.contains(ip))
+            && self.aaaa.iter().all(|ip| wildcard.aaaa.contains(ip))
+            && self.cname.iter().all(|c| wildcard.cname.contains(c))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub name: String,
+    /// Empty if the name does not exist
+    pub records: Records,
+    /// Answered like any name of a wildcard domain
+    pub wildcard: bool,
+}
+
+// Spaces the queries to keep the rate
+struct Limiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Limiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            interval: match rate {
+                0 => Duration::ZERO,
+                rate => Duration::from_secs(1) / rate,
+            },
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let at = (*next).max(Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        sleep_until(at).await;
+    }
+}
+
+pub struct Resolver {
+    config: Config,
+    limiter: Limiter,
+    counter: AtomicUsize,
+}
+
+impl Resolver {
+    pub fn new(config: Config) -> Self {
+        Self {
+            limiter: Limiter::new(config.rate),
+            config,
+            counter: AtomicUsize::new(0),
+        }
+    }
+
+    fn nonce(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        SystemTime::now().hash(&mut hasher);
+        self.counter
+            .fetch_add(1, Ordering::Relaxed)
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    async fn exchange(
+        &self,
+        server: SocketAddr,
+        name: &str,
+        qtype: u16,
+    ) -> Result<Option<Answer>, String> {
+        let id = self.nonce() as u16;
+        let bind = match server {
+            SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+            SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+        };
+        let socket = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+        socket.connect(server).await.map_err(|e| e.to_string())?;
+
+        self.limiter.wait().await;
+        let query = message::query(id, name, qtype)?;
+        socket.send(&query).await.map_err(|e| e.to_string())?;
+
+        let mut buf = [0; 4096];
+        let deadline = Instant::now() + self.config.timeout;
+        loop {
+            let len = timeout(deadline - Instant::now(), socket.recv(&mut buf))
+                .await
+                .map_err(|_| "Timeout".to_string())?
+                .map_err(|e| e.to_string())?;
+
+            match message::parse(&buf[..len], id) {
+                // Late answer of another query
+                Err("Unexpected id") => continue,
+                res => return res.map_err(String::from),
+            }
+        }
+    }
+
+    async fn lookup(&self, name: &str, qtype: u16) -> Result<Option<Answer>, String> {
+        let servers = &self.config.resolvers;
+        let first = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut reason = "No resolvers".to_string();
+
+        for attempt in 0..=self.config.retries {
+            let server = match servers.get((first + attempt) % servers.len().max(1)) {
+                Some(server) => *server,
+                None => break,
+            };
+            match self.exchange(server, name, qtype).await {
+                Ok(answer) => return Ok(answer),
+                Err(err) => {
+                    debug!("{} {}: {}", server, name, err);
+                    reason = err;
+                }
+            }
+        }
+
+        Err(reason)
+    }
+
+    /// A, AAAA and CNAME records of the name
+    pub async fn records(&self, name: &str) -> Result<Records, Error> {
+        let mut records = Records::default();
+
+        for qtype in [A, AAAA] {
+            match self.lookup(name, qtype).await {
+                Ok(Some(answer)) => records.extend(answer),
+                // No other type exists either
+                Ok(None) => break,
+                Err(reason) => {
+                    return Err(Error {
+                        name: name.to_string(),
+                        reason,
+                    })
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Records of random names of the domain, empty if it has no wildcard
+    pub async fn wildcard(&self, domain: &str) -> Result<Records, Error> {
+        let mut records = Records::default();
+
+        // Wildcards may answer round robin
+        for _ in 0..PROBES {
+            let name = format!("luna-{:016x}.{}", self.nonce(), domain);
+            let probe = self.records(&name).await?;
+            records.extend(Answer {
+                a: probe.a,
+                aaaa: probe.aaaa,
+                cname: probe.cname,
+            });
+        }
+
+        Ok(records)
+    }
+}
+
+const PROBES: usize = 3;
+
+fn root(name: &str) -> Option<String> {
+    parse_domain_name(name)
+        .ok()
+        .and_then(|d| d.root().map(String::from))
+}
+
+// Zone a wildcard answering the name would be in, a.dev.test.com -> dev.test.com
+fn zone(name: &str) -> Option<String> {
+    let root = root(name)?;
+    let (_, parent) = name.split_once('.')?;
+    parent.ends_with(&root).then(|| parent.to_string())
+}
+
+// Runs the tasks with at most limit of them at the same time
+async fn bounded<T: Send + 'static>(
+    tasks: impl Iterator<Item = impl std::future::Future<Output = T> + Send + 'static>,
+    limit: usize,
+) -> Vec<T> {
+    let mut running = JoinSet::new();
+    let mut results = vec![];
+    for task in tasks {
+        if running.len() >= limit.max(1) {
+            if let Some(Ok(res)) = running.join_next().await {
+                results.push(res);
+            }
+        }
+        running.spawn(task);
+    }
+    while let Some(res) = running.join_next().await {
+        if let Ok(res) = res {
+            results.push(res);
+        }
+    }
+    results
+}
+
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// In the order of the names
+    pub names: Vec<Result<Resolved, Error>>,
+    /// Fingerprint of each parent zone of the names, empty without a wildcard
+    pub wildcards: Vec<(String, Records)>,
+}
+
+/// Resolves the names concurrently after probing their parent zones for wildcards
+pub fn resolve(names: Vec<String>, config: Config) -> Resolution {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            return Resolution {
+                names: names
+                    .into_iter()
+                    .map(|name| {
+                        Err(Error {
+                            name,
+                            reason: err.to_string(),
+                        })
+                    })
+                    .collect(),
+                wildcards: vec![],
+            }
+        }
+    };
+
+    runtime.block_on(resolve_async(names, config))
+}
+
+pub async fn resolve_async(names: Vec<String>, config: Config) -> Resolution {
+    let concurrency = config.concurrency;
+    let resolver = Arc::new(Resolver::new(config));
+
+    // Wildcard records of each zone, e.g. *.dev.test.com
+    let mut zones: Vec<String> = names.iter().filter_map(|n| zone(n)).collect();
+    zones.sort();
+    zones.dedup();
+
+    let probes = zones.into_iter().map(|zone| {
+        let resolver = resolver.clone();
+        async move {
+            let res = resolver.wildcard(&zone).await;
+            (zone, res)
+        }
+    });
+    let mut wildcards = vec![];
+    for (zone, res) in bounded(probes, concurrency).await {
+        match res {
+            Ok(records) => {
+                if !records.is_empty() {
+                    warn!("Wildcard DNS: *.{}", zone);
+                }
+                wildcards.push((zone, records));
+            }
+            Err(err) => warn!("Wildcard check: {}", err),
+        }
+    }
+    wildcards.sort_by(|a, b| a.0.cmp(&b.0));
+    let wildcards = Arc::new(wildcards);
+
+    let tasks = names.into_iter().enumerate().map(|(i, name)| {
+        let (resolver, wildcards) = (resolver.clone(), wildcards.clone());
+        async move {
+            let res = resolver.records(&name).await.map(|records| {
+                let wildcard = zone(&name).is_some_and(|zone| {
+                    wildcards
+                        .iter()
+                        .any(|(z, w)| *z == zone && records.within(w))
+                });
+
+                Resolved {
+                    name,
+                    records,
+                    wildcard,
+                }
+            });
+            (i, res)
+        }
+    });
+    let mut results = bounded(tasks, concurrency).await;
+    results.sort_by_key(|(i, _)| *i);
+
+    Resolution {
+        names: results.into_iter().map(|(_, res)| res).collect(),
+        wildcards: Arc::try_unwrap(wildcards).unwrap_or_default(),
+    }
+}
+
+mod test {
+    #[allow(dead_code)]
+    fn labels(name: &str) -> Vec<u8> {
+        let mut buf = vec![];
+        for label in name.split('.') {
+            buf.push(label.len() as u8);
+            buf.extend(label.as_bytes());
+        }
+        buf.push(0);
+        buf
+    }
+
+    // Local DNS server answering from a small zone, *.wild.com is a wildcard
+    #[allow(dead_code)]
+    fn stub() -> std::net::SocketAddr {
+        use super::message::*;
+        use std::net::Ipv6Addr;
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        std::thread::spawn(move || loop {
+            let mut buf = [0; 512];
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            let (name, end) = read_name(&buf[..len], 12).unwrap();
+            let qtype = u16::from_be_bytes([buf[end], buf[end + 1]]);
+
+            let mut answers: Vec<(u16, Vec<u8>)> = vec![];
+            match (name.as_str(), qtype) {
+                ("fail.test.com", _) => continue,
+                ("a.test.com", A) => answers.push((A, vec![1, 2, 3, 4])),
+                ("a.test.com", AAAA) => answers.push((AAAA, Ipv6Addr::LOCALHOST.octets().to_vec())),
+                ("c.test.com", q) => {
+                    answers.push((CNAME, labels("a.test.com")));
+                    if q == A {
+                        answers.push((A, vec![1, 2, 3, 4]));
+                    }
+                }
+                ("real.wild.com", A) => answers.push((A, vec![5, 5, 5, 5])),
+                (n, A) if n.ends_with(".dev.deep.com") => answers.push((A, vec![7, 7, 7, 7])),
+                (n, A) if n.ends_with(".wild.com") => answers.push((A, vec![9, 9, 9, 9])),
+                _ => (),
+            }
+            let exists = !answers.is_empty()
+                || ["a.test.com", "real.wild.com"].contains(&name.as_str())
+                || name.ends_with(".wild.com")
+                || name.ends_with(".dev.deep.com");
+
+            let mut msg = buf[..2].to_vec();
+            msg.extend([0x81, if exists { 0x80 } else { 0x83 }]);
+            msg.extend([0, 1, 0, answers.len() as u8, 0, 0, 0, 0]);
+            msg.extend(&buf[12..end + 4]);
+            for (rtype, data) in answers {
+                msg.extend([0xC0, 12]);
+                msg.extend(rtype.to_be_bytes());
+                msg.extend([0, 1, 0, 0, 0, 60]);
+                msg.extend((data.len() as u16).to_be_bytes());
+                msg.extend(data);
+            }
+            socket.send_to(&msg, from).unwrap();
+        });
+
+        addr
+    }
+
+    #[test]
+    fn resolvers() {
+        use super::*;
+
+        let resolvers = Config::parse_resolvers(
+            "# comment\nnameserver 9.9.9.9\n1.1.1.1\n127.0.0.1:5353\noptions ndots:1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            resolvers,
+            vec![
+                SocketAddr::from(([9, 9, 9, 9], 53)),
+                SocketAddr::from(([1, 1, 1, 1], 53)),
+                SocketAddr::from(([127, 0, 0, 1], 5353)),
+            ]
+        );
+        assert!(Config::parse_resolvers("nameserver x").is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        use super::*;
+
+        let config = Config {
+            resolvers: vec![stub()],
+            timeout: Duration::from_millis(200),
+            retries: 1,
+            ..Default::default()
+        };
+        let names = [
+            "a.test.com",
+            "c.test.com",
+            "x.test.com",
+            "fail.test.com",
+            "real.wild.com",
+            "x.wild.com",
+            "x.dev.deep.com",
+            "dev.deep.com",
+        ];
+        let resolution = super::resolve(names.iter().map(|n| n.to_string()).collect(), config);
+        let results = resolution.names;
+
+        let a = results[0].as_ref().unwrap();
+        assert_eq!(a.records.a, vec![Ipv4Addr::new(1, 2, 3, 4)]);
+        assert_eq!(a.records.aaaa, vec![Ipv6Addr::LOCALHOST]);
+        assert!(!a.wildcard);
+
+        let c = results[1].as_ref().unwrap();
+        assert_eq!(c.records.cname, vec!["a.test.com"]);
+        assert_eq!(c.records.a, vec![Ipv4Addr::new(1, 2, 3, 4)]);
+
+        assert!(results[2].as_ref().unwrap().records.is_empty());
+        assert_eq!(results[3].as_ref().unwrap_err().reason, "Timeout");
+
+        assert!(!results[4].as_ref().unwrap().wildcard);
+        assert!(results[5].as_ref().unwrap().wildcard);
+
+        // Wildcard of a deeper label
+        assert!(results[6].as_ref().unwrap().wildcard);
+        assert!(results[7].as_ref().unwrap().records.is_empty());
+
+        let zones: Vec<&str> = resolution
+            .wildcards
+            .iter()
+            .map(|(z, _)| z.as_str())
+            .collect();
+        assert_eq!(
+            zones,
+            vec!["deep.com", "dev.deep.com", "test.com", "wild.com"]
+        );
+        assert!(resolution.wildcards[0].1.is_empty());
+        assert_eq!(resolution.wildcards[1].1.a, vec![Ipv4Addr::new(7, 7, 7, 7)]);
+        assert!(resolution.wildcards[2].1.is_empty());
+        assert_eq!(resolution.wildcards[3].1.a, vec![Ipv4Addr::new(9, 9, 9, 9)]);
+    }
+
+    #[test]
+    fn rate() {
+        use super::*;
+
+        let limiter = Limiter::new(20);
+        let start = std::time::Instant::now();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                for _ in 0..5 {
+                    limiter.wait().await;
+                }
+            });
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
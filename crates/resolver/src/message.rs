@@ -0,0 +1,151 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub const A: u16 = 1;
+pub const CNAME: u16 = 5;
+pub const AAAA: u16 = 28;
+
+const NXDOMAIN: u16 = 3;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Answer {
+    pub a: Vec<Ipv4Addr>,
+    pub aaaa: Vec<Ipv6Addr>,
+    pub cname: Vec<String>,
+}
+
+/// Recursive query for one name and type, labels are 1 to 63 bytes
+pub fn query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, &'static str> {
+    let mut buf = Vec::with_capacity(name.len() + 18);
+    buf.extend(id.to_be_bytes());
+    // Recursion desired
+    buf.extend(0x0100u16.to_be_bytes());
+    buf.extend(1u16.to_be_bytes());
+    buf.extend([0; 6]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err("Invalid label");
+        }
+        buf.push(label.len() as u8);
+        buf.extend(label.as_bytes());
+    }
+    buf.push(0);
+    // Names are 255 bytes at most
+    if buf.len() - 12 > 255 {
+        return Err("Name too long");
+    }
+
+    buf.extend(qtype.to_be_bytes());
+    buf.extend(1u16.to_be_bytes());
+    Ok(buf)
+}
+
+fn u16_at(buf: &[u8], i: usize) -> Result<u16, &'static str> {
+    buf.get(i..i + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or("Truncated message")
+}
+
+/// Name at i and the position after it, following compression pointers
+pub fn read_name(buf: &[u8], mut i: usize) -> Result<(String, usize), &'static str> {
+    let mut labels: Vec<String> = vec![];
+    let mut end = None;
+
+    // Bounds the pointers to follow
+    for _ in 0..128 {
+        let len = *buf.get(i).ok_or("Truncated name")? as usize;
+        match len {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(i + 1)));
+            }
+            l if l & 0xC0 == 0xC0 => {
+                let pointer = u16_at(buf, i)? as usize & 0x3FFF;
+                end.get_or_insert(i + 2);
+                i = pointer;
+            }
+            l => {
+                let label = buf.get(i + 1..i + 1 + l).ok_or("Truncated label")?;
+                labels.push(String::from_utf8_lossy(label).to_lowercase());
+                i += l + 1;
+            }
+        }
+    }
+
+    Err("Too many pointers")
+}
+
+/// None if the name does not exist
+pub fn parse(buf: &[u8], id: u16) -> Result<Option<Answer>, &'static str> {
+    if u16_at(buf, 0)? != id {
+        return Err("Unexpected id");
+    }
+    let flags = u16_at(buf, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err("Not a response");
+    }
+    match flags & 0x000F {
+        0 => (),
+        NXDOMAIN => return Ok(None),
+        _ => return Err("Server failure"),
+    }
+
+    let questions = u16_at(buf, 4)?;
+    let answers = u16_at(buf, 6)?;
+
+    let mut i = 12;
+    for _ in 0..questions {
+        i = read_name(buf, i)?.1 + 4;
+    }
+
+    let mut answer = Answer::default();
+    for _ in 0..answers {
+        i = read_name(buf, i)?.1;
+        let rtype = u16_at(buf, i)?;
+        let len = u16_at(buf, i + 8)? as usize;
+        i += 10;
+        let data = buf.get(i..i + len).ok_or("Truncated record")?;
+
+        match (rtype, len) {
+            (A, 4) => answer
+                .a
+                .push(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            (AAAA, 16) => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(data);
+                answer.aaaa.push(Ipv6Addr::from(octets));
+            }
+            (CNAME, _) => answer.cname.push(read_name(buf, i)?.0),
+            _ => (),
+        }
+        i += len;
+    }
+
+    Ok(Some(answer))
+}
+
+mod test {
+    #[test]
+    fn name() {
+        use super::*;
+
+        let q = query(7, "www.Test.com", A).unwrap();
+        assert_eq!(q.len(), 12 + 14 + 4);
+        assert_eq!(read_name(&q, 12), Ok(("www.test.com".to_string(), 26)));
+
+        // Pointer to the question
+        let mut msg = q.clone();
+        msg.extend([3, b'a', b'p', b'i', 0xC0, 16]);
+        assert_eq!(
+            read_name(&msg, q.len()),
+            Ok(("api.test.com".to_string(), q.len() + 6))
+        );
+
+        // Pointer loop
+        assert!(read_name(&[0xC0, 0], 0).is_err());
+
+        assert!(query(7, &format!("{}.test.com", "a".repeat(63)), A).is_ok());
+        assert!(query(7, &format!("{}.test.com", "a".repeat(64)), A).is_err());
+        assert!(query(7, "a..test.com", A).is_err());
+        assert!(query(7, &"a.".repeat(128), A).is_err());
+    }
+}